@@ -0,0 +1,360 @@
+use std::{
+    fmt::Debug,
+    fmt::Display,
+    fmt::Formatter,
+    io::{Read, Write},
+    mem::{align_of, offset_of, size_of},
+    os::raw::{c_float, c_int, c_long},
+};
+use binary_io::inspect::Registry;
+use binary_io::{BinPack, BinaryIO, BinaryIoError, FixedSize};
+use serde::{Deserialize, Serialize};
+
+mod query;
+pub use query::{Filter, RecordType, Stats, Summary};
+
+// the `type` values of structs.h
+pub const TYPE_VALUE: c_int = 1;
+pub const TYPE_MVALUE: c_int = 2;
+pub const TYPE_MESSAGE: c_int = 3;
+
+// `char message[21]`, a string of at most 20 bytes plus its NUL
+pub const MESSAGE_LEN: usize = 21;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, BinaryIO)]
+pub struct ValueStruct {
+    #[binary_io(assert = value_type == TYPE_VALUE)]
+    pub value_type: c_int,
+    pub val: c_float,
+    pub timestamp: c_long,
+}
+
+impl Display for ValueStruct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:.6}, timestamp: {}", self.val, self.timestamp))
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, BinaryIO)]
+pub struct MValueStruct {
+    #[binary_io(assert = value_type == TYPE_MVALUE)]
+    pub value_type: c_int,
+    pub val: [c_float; 10],
+    #[binary_io(pad = MVALUE_PADDING)]
+    pub mval: c_long,
+}
+
+impl Display for MValueStruct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut values = String::new();
+        for i in 0..10 {
+            values.push_str(&format!("{:.6} ", self.val[i]));
+        }
+        f.write_fmt(format_args!("{}, timestamp: {}", values, self.mval))
+    }
+}
+
+// the C compiler aligns `mval`, so there may be a hole between `val` and `mval`
+const MVALUE_PADDING: usize =
+    offset_of!(MValueStruct, mval) - offset_of!(MValueStruct, val) - size_of::<[c_float; 10]>();
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, BinaryIO)]
+pub struct SValueStruct {
+    #[binary_io(assert = value_type == TYPE_MESSAGE)]
+    pub value_type: c_int,
+    // NUL terminated by the C producer, but nothing in the file guarantees it
+    pub message: [u8; MESSAGE_LEN],
+}
+
+impl SValueStruct {
+    // the text up to the first NUL, or the whole buffer if there is none
+    pub fn message(&self) -> String {
+        let len = self
+            .message
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MESSAGE_LEN);
+        String::from_utf8_lossy(&self.message[..len]).into_owned()
+    }
+}
+
+impl Display for SValueStruct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}", self.message()))
+    }
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+// alignment and size of the anonymous union in `ExportData`
+const PAYLOAD_ALIGN: usize = max(
+    max(align_of::<ValueStruct>(), align_of::<MValueStruct>()),
+    align_of::<SValueStruct>(),
+);
+const PAYLOAD_SIZE: usize = max(
+    max(size_of::<ValueStruct>(), size_of::<MValueStruct>()),
+    size_of::<SValueStruct>(),
+)
+.next_multiple_of(PAYLOAD_ALIGN);
+// sizeof(ExportData): the type, padded up to the alignment of the union, and the union
+const EXPORT_DATA_SIZE: usize = size_of::<i32>().next_multiple_of(PAYLOAD_ALIGN) + PAYLOAD_SIZE;
+
+// safe counterpart of `ExportData`: `type` selects the active member of the union
+#[derive(Copy, Clone, Debug, PartialEq, BinaryIO)]
+#[binary_io(
+    tag = "i32",
+    payload_align = PAYLOAD_ALIGN,
+    payload_size = PAYLOAD_SIZE,
+    size = EXPORT_DATA_SIZE
+)]
+#[repr(i32)]
+pub enum CValue {
+    Value(ValueStruct) = TYPE_VALUE,
+    MValue(MValueStruct) = TYPE_MVALUE,
+    SValue(SValueStruct) = TYPE_MESSAGE,
+}
+
+impl Display for CValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CValue::Value(value) => f.write_fmt(format_args!("Value: {}", value)),
+            CValue::MValue(mvalue) => f.write_fmt(format_args!("MValue: {}", mvalue)),
+            CValue::SValue(svalue) => f.write_fmt(format_args!("Message: {}", svalue)),
+        }
+    }
+}
+
+// a record that could not be decoded, `offset` is where it starts in the file
+#[derive(Debug)]
+pub struct RecordError {
+    pub index: usize,
+    pub offset: u64,
+    pub error: BinaryIoError,
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "record {} at offset {:#x}: {}",
+            self.index, self.offset, self.error
+        )
+    }
+}
+
+impl std::error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// decodes the records of a file one by one, stopping at the first one that is invalid
+pub struct Records<R: Read> {
+    reader: R,
+    index: usize,
+    done: bool,
+}
+
+impl<R: Read> Records<R> {
+    pub fn new(reader: R) -> Self {
+        Records {
+            reader,
+            index: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Result<CValue, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match CValue::from_bytes(&mut self.reader) {
+            Ok(value) => {
+                self.index += 1;
+                Some(Ok(value))
+            }
+            Err(BinaryIoError::Eof) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(RecordError {
+                    index: self.index,
+                    offset: (self.index * CValue::ENCODED_SIZE) as u64,
+                    error,
+                }))
+            }
+        }
+    }
+}
+
+// what a record looks like in JSON and CSV, `type` picks the member of the union
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Record {
+    Value { val: f32, timestamp: c_long },
+    MValue { val: [f32; 10], timestamp: c_long },
+    Message { message: String },
+}
+
+impl From<&CValue> for Record {
+    fn from(value: &CValue) -> Self {
+        match value {
+            CValue::Value(v) => Record::Value {
+                val: v.val,
+                timestamp: v.timestamp,
+            },
+            CValue::MValue(v) => Record::MValue {
+                val: v.val,
+                timestamp: v.mval,
+            },
+            CValue::SValue(v) => Record::Message {
+                message: v.message(),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidMessage(pub String);
+
+impl Display for InvalidMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message {:?} must be at most {} bytes without NULs",
+            self.0,
+            MESSAGE_LEN - 1
+        )
+    }
+}
+
+impl std::error::Error for InvalidMessage {}
+
+impl TryFrom<Record> for CValue {
+    type Error = InvalidMessage;
+
+    fn try_from(record: Record) -> Result<Self, Self::Error> {
+        Ok(match record {
+            Record::Value { val, timestamp } => CValue::Value(ValueStruct {
+                value_type: TYPE_VALUE,
+                val,
+                timestamp,
+            }),
+            Record::MValue { val, timestamp } => CValue::MValue(MValueStruct {
+                value_type: TYPE_MVALUE,
+                val,
+                mval: timestamp,
+            }),
+            Record::Message { message } => {
+                if message.len() >= MESSAGE_LEN || message.contains('\0') {
+                    return Err(InvalidMessage(message));
+                }
+                let mut bytes = [0u8; MESSAGE_LEN];
+                bytes[..message.len()].copy_from_slice(message.as_bytes());
+                CValue::SValue(SValueStruct {
+                    value_type: TYPE_MESSAGE,
+                    message: bytes,
+                })
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // one line per record, like read_out.c
+    Text,
+    Json,
+    Csv,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv<W: Write>(out: &mut W, values: &[CValue]) -> std::io::Result<()> {
+    let columns: Vec<String> = (0..10).map(|i| format!("val{}", i)).collect();
+    writeln!(out, "type,timestamp,{},message", columns.join(","))?;
+    for value in values {
+        // unused columns are left empty
+        let (kind, timestamp, vals, message) = match Record::from(value) {
+            Record::Value { val, timestamp } => ("value", timestamp.to_string(), vec![val], None),
+            Record::MValue { val, timestamp } => {
+                ("mvalue", timestamp.to_string(), Vec::from(val), None)
+            }
+            Record::Message { message } => ("message", String::new(), vec![], Some(message)),
+        };
+        let mut fields: Vec<String> = vals.iter().map(|v| v.to_string()).collect();
+        fields.resize(10, String::new());
+        writeln!(
+            out,
+            "{},{},{},{}",
+            kind,
+            timestamp,
+            fields.join(","),
+            message.as_deref().map(csv_field).unwrap_or_default()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn export<W: Write>(out: &mut W, values: &[CValue], format: Format) -> std::io::Result<()> {
+    match format {
+        Format::Text => {
+            for value in values {
+                writeln!(out, "{}", value)?;
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let records: Vec<Record> = values.iter().map(Record::from).collect();
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)
+        }
+        Format::Csv => write_csv(out, values),
+    }
+}
+
+// reads a JSON array of records, as produced by `--format json`
+pub fn import_json<R: Read>(reader: R) -> Result<Vec<CValue>, Box<dyn std::error::Error>> {
+    let records: Vec<Record> = serde_json::from_reader(reader)?;
+    let mut values = Vec::with_capacity(records.len());
+    for record in records {
+        values.push(CValue::try_from(record)?);
+    }
+    Ok(values)
+}
+
+// the records of a file written by `write_records`, for binio-inspect
+pub fn schema_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register::<CValue>("export-data");
+    registry
+}
+
+// writes records in the layout of `ExportData`, so that read_out.c can read them back
+pub fn write_records<W: Write>(out: &mut W, values: &[CValue]) -> std::io::Result<()> {
+    for value in values {
+        value.to_bytes(out)?;
+    }
+    Ok(())
+}
@@ -1,108 +1,99 @@
-use std::{
-    fmt::Debug,
-    fmt::Display,
-    fmt::Formatter,
-    fs::File,
-    os::raw::{c_char, c_float, c_int, c_long},
-};
 use clap::Parser;
-use binary_io::{BinPack, BinaryIO, Write};
+use es1::{export, import_json, write_records, Filter, Format, RecordType, Records, Stats};
+use std::{fs::File, io::BufReader, io::BufWriter, io::Write, os::raw::c_long, process::exit};
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct ValueStruct {
-    value_type: c_int,
-    val: c_float,
-    timestamp: c_long,
-}
-
-impl Display for ValueStruct {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{:.6}, timestamp: {}", self.val, self.timestamp))
-    }
+#[derive(Parser)]
+struct Cli {
+    #[clap(short, long)]
+    file: String,
+    #[clap(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// generate `file` from a JSON array of records ("-" reads stdin) instead of reading it
+    #[clap(long, value_name = "JSON")]
+    write: Option<String>,
+    /// only records of these types
+    #[clap(long = "type", value_enum, value_delimiter = ',')]
+    types: Vec<RecordType>,
+    /// inclusive timestamp range, messages have no timestamp and are left out
+    #[clap(long, allow_hyphen_values = true)]
+    from: Option<c_long>,
+    /// end of the --from range
+    #[clap(long, allow_hyphen_values = true)]
+    to: Option<c_long>,
+    /// only messages containing this text
+    #[clap(long)]
+    contains: Option<String>,
+    /// report counts and statistics of the selected records instead of listing them
+    #[clap(long)]
+    stats: bool,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct MValueStruct {
-    value_type: c_int,
-    val: [c_float; 10],
-    mval: c_long,
+fn write_file(file: &str, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let values = if json == "-" {
+        import_json(std::io::stdin().lock())?
+    } else {
+        import_json(BufReader::new(File::open(json)?))?
+    };
+    let mut out = BufWriter::new(File::create(file)?);
+    write_records(&mut out, &values)?;
+    out.flush()?;
+    Ok(())
 }
 
-impl Display for MValueStruct {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut values = String::new();
-        for i in 0..10 {
-            values.push_str(&format!("{:.6} ", self.val[i]));
-        }
-        f.write_fmt(format_args!("{}, timestamp: {}", values, self.mval))
+fn read_file(
+    file: &str,
+    filter: &Filter,
+    format: Format,
+    stats: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if stats && format == Format::Csv {
+        return Err("--stats can only be printed as text or json".into());
     }
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct SValueStruct {
-    value_type: c_int,
-    message: [c_char; 21],
-}
-
-impl Display for SValueStruct {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let message = unsafe { std::ffi::CStr::from_ptr(self.message.as_ptr()) };
-        match message.to_str() {
-            Ok(s) => f.write_fmt(format_args!("{}", s)),
-            Err(_) => Err(std::fmt::Error),
+    let reader = BufReader::new(File::open(file)?);
+    let mut imported_data = vec![];
+    let mut failure = None;
+    for record in Records::new(reader) {
+        match record {
+            Ok(data) if filter.matches(&data) => imported_data.push(data),
+            Ok(_) => {}
+            Err(e) => failure = Some(e),
         }
     }
-}
-
-#[repr(C)]
-#[derive(Copy, Clone)]
-union ValueUnion {
-    value: ValueStruct,
-    mvalue: MValueStruct,
-    svalue: SValueStruct,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, BinaryIO)]
-struct CData {
-    value_type: c_int,
-    value: ValueUnion,
-}
-
-impl Display for CData {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        unsafe {
-            match self.value_type {
-                1 => f.write_fmt(format_args!("Value: {}", self.value.value)),
-                2 => f.write_fmt(format_args!("MValue: {}", self.value.mvalue)),
-                3 => f.write_fmt(format_args!("Message: {}", self.value.svalue)),
-                _ => f.write_str("Unknown"),
-            }
+    // whatever was decoded before the bad record is still exported
+    let mut out = std::io::stdout().lock();
+    if stats {
+        let stats = Stats::of(&imported_data);
+        if format == Format::Json {
+            serde_json::to_writer_pretty(&mut out, &stats)?;
+            writeln!(out)?;
+        } else {
+            write!(out, "{}", stats)?;
         }
+    } else {
+        export(&mut out, &imported_data, format)?;
+    }
+    match failure {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
     }
-}
-
-#[derive(Parser)]
-struct Cli {
-    #[clap(short, long)]
-    file: String,
 }
 
 fn main() {
     let args = Cli::parse();
-    let f = match File::open(args.file) {
-        Ok(f) => f,
-        Err(e) => panic!("Error opening file: {}", e),
+    let result = match &args.write {
+        Some(json) => write_file(&args.file, json),
+        None => {
+            let filter = Filter {
+                types: args.types,
+                from: args.from,
+                to: args.to,
+                contains: args.contains,
+            };
+            read_file(&args.file, &filter, args.format, args.stats)
+        }
     };
-    let mut reader = std::io::BufReader::new(f);
-    let mut imported_data = vec![];
-    while let Some(data) = CData::from_bytes(&mut reader) {
-        imported_data.push(data);
-    }
-    for data in imported_data.iter() {
-        println!("{}", data);
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        exit(1);
     }
 }
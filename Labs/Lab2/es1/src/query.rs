@@ -0,0 +1,182 @@
+use crate::CValue;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_long;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RecordType {
+    Value,
+    #[value(name = "mvalue")]
+    MValue,
+    Message,
+}
+
+impl CValue {
+    pub fn record_type(&self) -> RecordType {
+        match self {
+            CValue::Value(_) => RecordType::Value,
+            CValue::MValue(_) => RecordType::MValue,
+            CValue::SValue(_) => RecordType::Message,
+        }
+    }
+
+    // messages carry no timestamp
+    pub fn timestamp(&self) -> Option<c_long> {
+        match self {
+            CValue::Value(v) => Some(v.timestamp),
+            CValue::MValue(v) => Some(v.mval),
+            CValue::SValue(_) => None,
+        }
+    }
+}
+
+// every condition that is set must hold, an empty filter matches everything
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    // any of these types, all of them if empty
+    pub types: Vec<RecordType>,
+    // inclusive bounds on the timestamp, records without one never match a range
+    pub from: Option<c_long>,
+    pub to: Option<c_long>,
+    // only messages containing this text
+    pub contains: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, value: &CValue) -> bool {
+        if !self.types.is_empty() && !self.types.contains(&value.record_type()) {
+            return false;
+        }
+        if self.from.is_some() || self.to.is_some() {
+            match value.timestamp() {
+                Some(t)
+                    if self.from.is_none_or(|from| t >= from)
+                        && self.to.is_none_or(|to| t <= to) => {}
+                _ => return false,
+            }
+        }
+        match (&self.contains, value) {
+            (None, _) => true,
+            (Some(text), CValue::SValue(s)) => s.message().contains(text.as_str()),
+            (Some(_), _) => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+}
+
+impl Summary {
+    fn new(x: f32) -> Self {
+        Summary {
+            count: 1,
+            min: x,
+            max: x,
+            mean: x as f64,
+        }
+    }
+
+    fn push(&mut self, x: f32) {
+        self.count += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        // running mean, so that large files do not lose precision in a huge sum
+        self.mean += (x as f64 - self.mean) / self.count as f64;
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "min {:.6}, max {:.6}, mean {:.6}",
+            self.min, self.max, self.mean
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub values: usize,
+    pub mvalues: usize,
+    pub messages: usize,
+    // `val` of the Value records
+    pub val: Option<Summary>,
+    // each of the 10 slots of the MValue records
+    pub slots: Option<[Summary; 10]>,
+    // earliest and latest timestamp
+    pub time_span: Option<(c_long, c_long)>,
+}
+
+impl Stats {
+    pub fn of<'a>(values: impl IntoIterator<Item = &'a CValue>) -> Self {
+        let mut stats = Stats::default();
+        for value in values {
+            stats.add(value);
+        }
+        stats
+    }
+
+    pub fn add(&mut self, value: &CValue) {
+        match value {
+            CValue::Value(v) => {
+                self.values += 1;
+                match &mut self.val {
+                    Some(summary) => summary.push(v.val),
+                    None => self.val = Some(Summary::new(v.val)),
+                }
+            }
+            CValue::MValue(v) => {
+                self.mvalues += 1;
+                match &mut self.slots {
+                    Some(slots) => slots.iter_mut().zip(v.val).for_each(|(s, x)| s.push(x)),
+                    None => self.slots = Some(v.val.map(Summary::new)),
+                }
+            }
+            CValue::SValue(_) => self.messages += 1,
+        }
+        if let Some(t) = value.timestamp() {
+            self.time_span = match self.time_span {
+                Some((first, last)) => Some((first.min(t), last.max(t))),
+                None => Some((t, t)),
+            };
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.values + self.mvalues + self.messages
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "records: {}", self.total())?;
+        writeln!(f, "  value: {}", self.values)?;
+        writeln!(f, "  mvalue: {}", self.mvalues)?;
+        writeln!(f, "  message: {}", self.messages)?;
+        if let Some(val) = &self.val {
+            writeln!(f, "value: {}", val)?;
+        }
+        if let Some(slots) = &self.slots {
+            writeln!(f, "mvalue:")?;
+            for (i, slot) in slots.iter().enumerate() {
+                writeln!(f, "  val[{}]: {}", i, slot)?;
+            }
+        }
+        match self.time_span {
+            Some((first, last)) => writeln!(
+                f,
+                "time span: {} .. {} ({})",
+                first,
+                last,
+                last as i128 - first as i128
+            ),
+            None => writeln!(f, "time span: none"),
+        }
+    }
+}
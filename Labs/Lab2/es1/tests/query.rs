@@ -0,0 +1,123 @@
+use es1::{CValue, Filter, Record, RecordType, Stats};
+
+fn value(val: f32, timestamp: i64) -> CValue {
+    CValue::try_from(Record::Value {
+        val,
+        timestamp: timestamp as _,
+    })
+    .unwrap()
+}
+
+fn mvalue(first: f32, timestamp: i64) -> CValue {
+    let mut val = [1.0; 10];
+    val[0] = first;
+    CValue::try_from(Record::MValue {
+        val,
+        timestamp: timestamp as _,
+    })
+    .unwrap()
+}
+
+fn message(text: &str) -> CValue {
+    CValue::try_from(Record::Message {
+        message: text.to_string(),
+    })
+    .unwrap()
+}
+
+fn sample() -> Vec<CValue> {
+    vec![
+        value(1.0, 100),
+        mvalue(2.0, 50),
+        message("disk almost full"),
+        value(3.0, 300),
+        mvalue(4.0, 200),
+        message("all good"),
+    ]
+}
+
+fn select(filter: &Filter) -> Vec<CValue> {
+    sample().into_iter().filter(|v| filter.matches(v)).collect()
+}
+
+#[test]
+fn empty_filter_matches_everything() {
+    assert_eq!(select(&Filter::default()), sample());
+}
+
+#[test]
+fn filter_by_type() {
+    let filter = Filter {
+        types: vec![RecordType::Value, RecordType::Message],
+        ..Filter::default()
+    };
+    let types: Vec<_> = select(&filter).iter().map(CValue::record_type).collect();
+    assert_eq!(
+        types,
+        [
+            RecordType::Value,
+            RecordType::Message,
+            RecordType::Value,
+            RecordType::Message
+        ]
+    );
+}
+
+#[test]
+fn filter_by_timestamp_range() {
+    let filter = Filter {
+        from: Some(100),
+        to: Some(200),
+        ..Filter::default()
+    };
+    // bounds are inclusive and messages have no timestamp
+    assert_eq!(select(&filter), [value(1.0, 100), mvalue(4.0, 200)]);
+    let filter = Filter {
+        from: Some(250),
+        ..Filter::default()
+    };
+    assert_eq!(select(&filter), [value(3.0, 300)]);
+}
+
+#[test]
+fn filter_by_message() {
+    let filter = Filter {
+        contains: Some("full".to_string()),
+        ..Filter::default()
+    };
+    assert_eq!(select(&filter), [message("disk almost full")]);
+    let filter = Filter {
+        contains: Some("full".to_string()),
+        types: vec![RecordType::Value],
+        ..Filter::default()
+    };
+    assert!(select(&filter).is_empty());
+}
+
+#[test]
+fn stats() {
+    let stats = Stats::of(&sample());
+    assert_eq!(
+        (stats.values, stats.mvalues, stats.messages, stats.total()),
+        (2, 2, 2, 6)
+    );
+    let val = stats.val.unwrap();
+    assert_eq!((val.count, val.min, val.max, val.mean), (2, 1.0, 3.0, 2.0));
+    let slots = stats.slots.unwrap();
+    assert_eq!((slots[0].min, slots[0].max, slots[0].mean), (2.0, 4.0, 3.0));
+    assert_eq!((slots[9].min, slots[9].max, slots[9].mean), (1.0, 1.0, 1.0));
+    assert_eq!(stats.time_span, Some((50, 300)));
+    let text = stats.to_string();
+    assert!(text.contains("value: min 1.000000, max 3.000000, mean 2.000000"));
+    assert!(text.contains("time span: 50 .. 300 (250)"));
+}
+
+#[test]
+fn stats_of_nothing() {
+    let stats = Stats::of(&[message("only text")]);
+    assert_eq!(stats.total(), 1);
+    assert_eq!(stats.val, None);
+    assert_eq!(stats.slots, None);
+    assert_eq!(stats.time_span, None);
+    assert!(stats.to_string().contains("time span: none"));
+}
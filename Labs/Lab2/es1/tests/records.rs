@@ -0,0 +1,162 @@
+use binary_io::{BinPack, BinaryIoError, FixedSize};
+use es1::{
+    export, import_json, write_records, CValue, Format, Record, Records, SValueStruct,
+    ValueStruct, MESSAGE_LEN, TYPE_MESSAGE, TYPE_VALUE,
+};
+
+fn sample() -> Vec<CValue> {
+    vec![
+        Record::Value {
+            val: 1.5,
+            timestamp: 10,
+        },
+        Record::MValue {
+            val: [0.25; 10],
+            timestamp: 20,
+        },
+        Record::Message {
+            message: "hello, \"world\"".to_string(),
+        },
+    ]
+    .into_iter()
+    .map(|r| CValue::try_from(r).unwrap())
+    .collect()
+}
+
+fn encoded(values: &[CValue]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_records(&mut bytes, values).unwrap();
+    bytes
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+fn layout_matches_export_data() {
+    // sizeof(ExportData) with gcc on 64 bit linux
+    assert_eq!(CValue::ENCODED_SIZE, 64);
+}
+
+#[test]
+fn roundtrip() {
+    let values = sample();
+    let bytes = encoded(&values);
+    assert_eq!(bytes.len(), 3 * CValue::ENCODED_SIZE);
+    let decoded: Vec<_> = Records::new(&bytes[..]).map(Result::unwrap).collect();
+    assert_eq!(decoded, values);
+}
+
+#[test]
+fn unterminated_message_is_bounded() {
+    let mut bytes = encoded(&sample()[2..]);
+    // the payload is 8 aligned, the message follows its type field
+    for b in &mut bytes[12..12 + MESSAGE_LEN] {
+        *b = b'x';
+    }
+    let (value, _) = CValue::from_slice(&bytes).unwrap();
+    match value {
+        CValue::SValue(s) => assert_eq!(s.message(), "x".repeat(MESSAGE_LEN)),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_type_is_reported_with_its_offset() {
+    let mut bytes = encoded(&sample());
+    bytes[2 * CValue::ENCODED_SIZE..][..4].copy_from_slice(&7i32.to_ne_bytes());
+    let mut records = Records::new(&bytes[..]);
+    assert!(records.next().unwrap().is_ok());
+    assert!(records.next().unwrap().is_ok());
+    let error = records.next().unwrap().unwrap_err();
+    assert_eq!(error.index, 2);
+    assert_eq!(error.offset, 2 * CValue::ENCODED_SIZE as u64);
+    assert!(matches!(
+        error.error,
+        BinaryIoError::InvalidDiscriminant { .. }
+    ));
+    assert!(error
+        .to_string()
+        .starts_with(&format!("record 2 at offset {:#x}", error.offset)));
+    assert!(records.next().is_none());
+}
+
+#[test]
+fn inner_type_must_match_the_outer_one() {
+    let value = CValue::SValue(SValueStruct {
+        value_type: TYPE_VALUE,
+        message: [0; MESSAGE_LEN],
+    });
+    let bytes = value.to_vec().unwrap();
+    assert!(matches!(
+        CValue::from_slice(&bytes),
+        Err(BinaryIoError::AssertionFailed { .. })
+    ));
+}
+
+#[test]
+fn truncated_file() {
+    let bytes = encoded(&sample());
+    let mut records = Records::new(&bytes[..bytes.len() - 1]);
+    records.next().unwrap().unwrap();
+    records.next().unwrap().unwrap();
+    let error = records.next().unwrap().unwrap_err();
+    assert_eq!(error.offset, 2 * CValue::ENCODED_SIZE as u64);
+}
+
+#[test]
+fn json_roundtrip() {
+    let values = sample();
+    let mut json = vec![];
+    export(&mut json, &values, Format::Json).unwrap();
+    let text = String::from_utf8(json.clone()).unwrap();
+    assert!(text.contains("\"type\": \"mvalue\""));
+    assert_eq!(import_json(&json[..]).unwrap(), values);
+}
+
+#[test]
+fn csv_output() {
+    let mut csv = vec![];
+    export(&mut csv, &sample(), Format::Csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "type,timestamp,val0,val1,val2,val3,val4,val5,val6,val7,val8,val9,message"
+    );
+    assert_eq!(lines[1], "value,10,1.5,,,,,,,,,,");
+    assert_eq!(lines[3], "message,,,,,,,,,,,,\"hello, \"\"world\"\"\"");
+}
+
+#[test]
+fn text_output() {
+    let mut text = vec![];
+    let value = CValue::Value(ValueStruct {
+        value_type: TYPE_VALUE,
+        val: 0.5,
+        timestamp: 3,
+    });
+    export(&mut text, &[value], Format::Text).unwrap();
+    assert_eq!(text, b"Value: 0.500000, timestamp: 3\n");
+}
+
+#[test]
+fn invalid_messages_are_rejected() {
+    let too_long = Record::Message {
+        message: "a".repeat(MESSAGE_LEN),
+    };
+    assert!(CValue::try_from(too_long).is_err());
+    let with_nul = Record::Message {
+        message: "a\0b".to_string(),
+    };
+    assert!(CValue::try_from(with_nul).is_err());
+    let longest = Record::Message {
+        message: "a".repeat(MESSAGE_LEN - 1),
+    };
+    match CValue::try_from(longest).unwrap() {
+        CValue::SValue(s) => {
+            assert_eq!(s.value_type, TYPE_MESSAGE);
+            assert_eq!(s.message[MESSAGE_LEN - 1], 0);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(import_json(&br#"[{"type": "other"}]"#[..]).is_err());
+}
@@ -6,56 +6,806 @@ extern crate syn;
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use syn::DeriveInput;
-
-fn check_if_attr_is_repr_c(attr: &syn::Attribute) -> bool {
-    if attr.path().is_ident("repr") {
-        let nested = attr.parse_args_with(syn::punctuated::Punctuated::<syn::Meta, Token![,]>::parse_terminated);
-        if let Ok(nested) = nested {
-            for meta in nested.iter() {
-                if let syn::Meta::Path(path) = meta {
-                    if path.is_ident("C") {
-                        return true;
+use syn::{Data, DeriveInput, Fields};
+
+#[derive(Default)]
+struct ContainerAttrs {
+    endian: Option<proc_macro2::TokenStream>,
+    tag: Option<syn::Type>,
+    payload_align: Option<syn::Expr>,
+    payload_size: Option<syn::Expr>,
+    magic: Option<syn::LitByteStr>,
+    version: Option<syn::Expr>,
+    // fn(version, payload, endian) -> Result<Option<Self>, BinaryIoError>, tried on records older than `version`
+    migrate: Option<syn::Path>,
+    // expected encoded size, checked at compile time
+    size: Option<syn::Expr>,
+}
+
+impl ContainerAttrs {
+    // whether the first read of the body may hit a clean EOF, i.e. nothing is read before it
+    fn body_may_eof(&self) -> bool {
+        self.magic.is_none() && self.version.is_none()
+    }
+}
+
+const TAG_TYPES: [&str; 8] = ["u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64"];
+
+fn parse_container_attrs(attrs: &[syn::Attribute]) -> syn::Result<ContainerAttrs> {
+    let mut container = ContainerAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("binary_io")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("endian") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                container.endian = Some(match value.value().as_str() {
+                    "little" => quote! { ::binary_io::Endian::Little },
+                    "big" => quote! { ::binary_io::Endian::Big },
+                    "native" => quote! { ::binary_io::Endian::NATIVE },
+                    _ => {
+                        return Err(
+                            meta.error("endian must be one of \"little\", \"big\" or \"native\"")
+                        )
                     }
+                });
+                Ok(())
+            } else if meta.path.is_ident("tag") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                if !TAG_TYPES.contains(&value.value().as_str()) {
+                    return Err(
+                        meta.error("tag must be an integer type of at most 64 bits, e.g. \"i32\"")
+                    );
                 }
+                container.tag = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("payload_align") {
+                container.payload_align = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("payload_size") {
+                container.payload_size = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("magic") {
+                container.magic = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("version") {
+                container.version = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("migrate") {
+                container.migrate = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("size") {
+                container.size = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown binary_io attribute"))
+            }
+        })?;
+    }
+    Ok(container)
+}
+
+// adds a `BinPack` bound to every type parameter so that generic containers
+// can be serialized as long as their contents can.
+fn add_trait_bounds(mut generics: syn::Generics) -> syn::Generics {
+    for param in &mut generics.params {
+        if let syn::GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(parse_quote!(::binary_io::BinPack));
+        }
+    }
+    generics
+}
+
+// names the fields of a struct (`self.x`, `self.0`) or binds them in a match arm (`x`, `__field0`)
+fn field_accessors(fields: &Fields) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = syn::Index::from(i);
+                quote! { #index }
+            }
+        })
+        .collect()
+}
+
+fn field_bindings(fields: &Fields) -> Vec<syn::Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("__field{}", i),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    len: Option<proc_macro2::TokenStream>,
+    // not persisted, `Default::default()` on read
+    skip: bool,
+    // constant bytes written before the field and checked on read
+    magic: Option<syn::LitByteStr>,
+    // zero bytes written before the field and ignored on read
+    pad: Option<syn::Expr>,
+    // checked right after the field is read, may refer to the fields read so far
+    assert: Option<syn::Expr>,
+    // first version of the container that has the field, older records get `Default::default()`
+    since: Option<syn::Expr>,
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("binary_io"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("len") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                attrs.len = Some(match value.value().as_str() {
+                    "u8" => quote! { ::binary_io::LengthPrefix::U8 },
+                    "u16" => quote! { ::binary_io::LengthPrefix::U16 },
+                    "u32" => quote! { ::binary_io::LengthPrefix::U32 },
+                    "varint" => quote! { ::binary_io::LengthPrefix::Varint },
+                    _ => {
+                        return Err(
+                            meta.error("len must be one of \"u8\", \"u16\", \"u32\" or \"varint\"")
+                        )
+                    }
+                });
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                attrs.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("magic") {
+                attrs.magic = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("pad") {
+                attrs.pad = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("assert") {
+                attrs.assert = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("since") {
+                attrs.since = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown binary_io field attribute"))
+            }
+        })?;
+    }
+    Ok(attrs)
+}
+
+fn gen_write_fields(
+    fields: &Fields,
+    values: &[proc_macro2::TokenStream],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut writes = vec![];
+    for (field, value) in fields.iter().zip(values) {
+        let attrs = parse_field_attrs(field)?;
+        if let Some(pad) = &attrs.pad {
+            writes.push(quote! { std::io::Write::write_all(__writer, &[0u8; #pad])?; });
+        }
+        if let Some(magic) = &attrs.magic {
+            writes.push(quote! { std::io::Write::write_all(__writer, #magic)?; });
+        }
+        if attrs.skip {
+            continue;
+        }
+        writes.push(match attrs.len {
+            Some(prefix) => quote! {
+                ::binary_io::LengthPrefixed::to_bytes_prefixed(#value, __writer, __endian, #prefix)?;
+            },
+            None => quote! {
+                ::binary_io::BinPack::to_bytes_endian(#value, __writer, __endian)?;
+            },
+        });
+    }
+    Ok(quote! { #(#writes)* })
+}
+
+// a stretch of the encoded form: a field, or the magic or padding in front of one
+struct Region {
+    name: String,
+    type_name: proc_macro2::TokenStream,
+    size: proc_macro2::TokenStream,
+}
+
+// token streams print with a space between every token, `[f32 ; 10]` reads better as `[f32; 10]`
+fn type_name(ty: &impl quote::ToTokens) -> String {
+    let mut name = quote::ToTokens::to_token_stream(ty).to_string();
+    for (from, to) in [
+        (" ;", ";"),
+        (" ,", ","),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("( ", "("),
+        (" )", ")"),
+        ("& ", "&"),
+        (" :: ", "::"),
+        (":: ", "::"),
+    ] {
+        name = name.replace(from, to);
+    }
+    name
+}
+
+fn magic_region(magic: &syn::LitByteStr) -> Region {
+    let len = magic.value().len();
+    let type_name = format!("[u8; {}]", len);
+    Region {
+        name: "(magic)".to_string(),
+        type_name: quote! { #type_name },
+        size: quote! { Some(#len) },
+    }
+}
+
+fn field_regions(fields: &Fields) -> syn::Result<Vec<Region>> {
+    let mut regions = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = parse_field_attrs(field)?;
+        if let Some(pad) = &attrs.pad {
+            regions.push(Region {
+                name: "(padding)".to_string(),
+                type_name: quote! { concat!("[u8; ", stringify!(#pad), "]") },
+                size: quote! { Some(#pad) },
+            });
+        }
+        if let Some(magic) = &attrs.magic {
+            regions.push(magic_region(magic));
+        }
+        if attrs.skip {
+            continue;
+        }
+        let ty = &field.ty;
+        let ty_name = type_name(ty);
+        regions.push(Region {
+            name: match &field.ident {
+                Some(ident) => ident.to_string(),
+                None => i.to_string(),
+            },
+            type_name: quote! { #ty_name },
+            size: match attrs.len {
+                Some(_) => quote! { None },
+                None => quote! { <#ty as ::binary_io::BinPack>::STATIC_SIZE },
+            },
+        });
+    }
+    Ok(regions)
+}
+
+// lays the regions out back to back, giving the SCHEMA entries and the total size
+fn gen_schema(regions: &[Region]) -> (Vec<proc_macro2::TokenStream>, proc_macro2::TokenStream) {
+    let mut offset = quote! { Some(0usize) };
+    let mut entries = vec![];
+    for Region {
+        name,
+        type_name,
+        size,
+    } in regions
+    {
+        entries.push(quote! {
+            ::binary_io::FieldInfo {
+                name: #name,
+                offset: #offset,
+                size: #size,
+                type_name: #type_name,
             }
+        });
+        offset = quote! { ::binary_io::__private::add(#offset, #size) };
+    }
+    (entries, offset)
+}
+
+// only the first read of a record may report a clean EOF, anything after it means the record was cut short
+struct EofTracker(bool);
+
+impl EofTracker {
+    fn next(&mut self) -> proc_macro2::TokenStream {
+        let may_eof = std::mem::replace(&mut self.0, false);
+        if may_eof {
+            quote! {}
+        } else {
+            quote! { .map_err(::binary_io::BinaryIoError::within_record) }
+        }
+    }
+}
+
+// fields are read in order into locals named after them, then moved into the value.
+fn gen_read_fields(
+    type_name: &syn::Ident,
+    ctor: proc_macro2::TokenStream,
+    fields: &Fields,
+    first_may_eof: bool,
+    versioned: bool,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let bindings = field_bindings(fields);
+    let mut reads = vec![];
+    let mut eof = EofTracker(first_may_eof);
+    for (field, binding) in fields.iter().zip(&bindings) {
+        let attrs = parse_field_attrs(field)?;
+        let ty = &field.ty;
+        if let Some(pad) = &attrs.pad {
+            let eof = eof.next();
+            reads.push(quote! { ::binary_io::__private::skip(__reader, #pad) #eof?; });
+        }
+        if let Some(magic) = &attrs.magic {
+            let eof = eof.next();
+            reads.push(quote! { ::binary_io::__private::expect_magic(__reader, #magic) #eof?; });
+        }
+        if attrs.skip {
+            reads.push(quote! { let #binding = <#ty as Default>::default(); });
+        } else {
+            let read = match attrs.len {
+                Some(prefix) => quote! {
+                    <#ty as ::binary_io::LengthPrefixed>::from_bytes_prefixed(__reader, __endian, #prefix)
+                },
+                None => {
+                    quote! { <#ty as ::binary_io::BinPack>::from_bytes_endian(__reader, __endian) }
+                }
+            };
+            let eof = eof.next();
+            reads.push(match &attrs.since {
+                Some(since) if versioned => quote! {
+                    let #binding = if __version >= (#since) as u16 {
+                        #read #eof?
+                    } else {
+                        <#ty as Default>::default()
+                    };
+                },
+                Some(since) => {
+                    return Err(syn::Error::new_spanned(
+                        since,
+                        "since needs a versioned container, e.g. #[binary_io(version = 2)]",
+                    ))
+                }
+                None => quote! { let #binding = #read #eof?; },
+            });
+        }
+        if let Some(assert) = &attrs.assert {
+            reads.push(quote! {
+                if !(#assert) {
+                    return Err(::binary_io::BinaryIoError::AssertionFailed {
+                        field: concat!(stringify!(#type_name), ".", stringify!(#binding)),
+                        condition: stringify!(#assert),
+                    });
+                }
+            });
+        }
+    }
+    let value = match fields {
+        Fields::Named(_) => quote! { #ctor { #(#bindings),* } },
+        Fields::Unnamed(_) => quote! { #ctor(#(#bindings),*) },
+        Fields::Unit => quote! { #ctor },
+    };
+    Ok(quote! {
+        #(#reads)*
+        Ok(#value)
+    })
+}
+
+struct Generated {
+    to_bytes: proc_macro2::TokenStream,
+    from_bytes: proc_macro2::TokenStream,
+    regions: Vec<Region>,
+}
+
+fn gen_struct(
+    name: &syn::Ident,
+    fields: &Fields,
+    container: &ContainerAttrs,
+) -> syn::Result<Generated> {
+    let values: Vec<_> = field_accessors(fields)
+        .into_iter()
+        .map(|a| quote! { &self.#a })
+        .collect();
+    Ok(Generated {
+        to_bytes: gen_write_fields(fields, &values)?,
+        from_bytes: gen_read_fields(
+            name,
+            quote! { Self },
+            fields,
+            container.body_may_eof(),
+            container.version.is_some(),
+        )?,
+        regions: field_regions(fields)?,
+    })
+}
+
+// explicit discriminants are honoured, the others follow the previous one like rustc does
+fn variant_discriminants(data: &syn::DataEnum) -> Vec<proc_macro2::TokenStream> {
+    let mut last: Option<proc_macro2::TokenStream> = None;
+    data.variants
+        .iter()
+        .map(|v| {
+            let discriminant = match (&v.discriminant, &last) {
+                (Some((_, expr)), _) => quote! { (#expr) },
+                (None, Some(prev)) => quote! { (#prev + 1) },
+                (None, None) => quote! { 0 },
+            };
+            last = Some(discriminant.clone());
+            discriminant
+        })
+        .collect()
+}
+
+// an enum is written as its discriminant, optionally padded so that the payload is aligned,
+// followed by the fields of the active variant, optionally padded to a fixed size like a C union.
+fn gen_enum(
+    name: &syn::Ident,
+    data: &syn::DataEnum,
+    container: &ContainerAttrs,
+) -> syn::Result<Generated> {
+    let tag = match &container.tag {
+        Some(tag) => tag,
+        None => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "enums need a discriminant type, e.g. #[binary_io(tag = \"i32\")]",
+            ))
         }
+    };
+    let discriminants = variant_discriminants(data);
+    let tag_eof = EofTracker(container.body_may_eof()).next();
+    let tag_padding = match &container.payload_align {
+        Some(align) => quote! {
+            (#align - std::mem::size_of::<#tag>() % #align) % #align
+        },
+        None => quote! { 0 },
+    };
+
+    let mut write_arms = vec![];
+    let mut read_arms = vec![];
+    // the payload only has a static size if every variant has the same one
+    let mut variant_size: Option<proc_macro2::TokenStream> = None;
+    for (variant, discriminant) in data.variants.iter().zip(discriminants.iter()) {
+        let ident = &variant.ident;
+        let (_, size) = gen_schema(&field_regions(&variant.fields)?);
+        variant_size = Some(match variant_size {
+            Some(prev) => quote! { ::binary_io::__private::same(#prev, #size) },
+            None => size,
+        });
+        let bindings = field_bindings(&variant.fields);
+        let pattern = match &variant.fields {
+            Fields::Named(_) => quote! { Self::#ident { #(#bindings),* } },
+            Fields::Unnamed(_) => quote! { Self::#ident(#(#bindings),*) },
+            Fields::Unit => quote! { Self::#ident },
+        };
+        let values: Vec<_> = bindings.iter().map(|b| quote! { #b }).collect();
+        let write = gen_write_fields(&variant.fields, &values)?;
+        write_arms.push(quote! {
+            #pattern => {
+                ::binary_io::BinPack::to_bytes_endian(&(#discriminant as #tag), __tag_writer, __endian)?;
+                __tag_writer.write_all(&[0u8; #tag_padding])?;
+                #write
+            }
+        });
+        let read = gen_read_fields(
+            name,
+            quote! { Self::#ident },
+            &variant.fields,
+            false,
+            container.version.is_some(),
+        )?;
+        read_arms.push(quote! {
+            if __tag == (#discriminant as #tag) {
+                return { #read };
+            }
+        });
+    }
+
+    let (write_payload, read_payload) = match &container.payload_size {
+        Some(size) => (
+            quote! {
+                let __tag_writer = __writer;
+                let mut __payload = Vec::new();
+                let __writer = &mut __payload;
+                match self {
+                    #(#write_arms)*
+                }
+                if __payload.len() > #size {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} payload of {} bytes does not fit in {} bytes",
+                            stringify!(#name),
+                            __payload.len(),
+                            #size
+                        ),
+                    ));
+                }
+                __payload.resize(#size, 0);
+                __tag_writer.write_all(&__payload)?;
+            },
+            quote! {
+                let mut __payload = [0u8; #size];
+                ::binary_io::read_exact(__reader, &mut __payload)
+                    .map_err(::binary_io::BinaryIoError::within_record)?;
+                let __reader = &mut &__payload[..];
+            },
+        ),
+        None => (
+            quote! {
+                let __tag_writer = &mut *__writer;
+                match self {
+                    #(#write_arms)*
+                }
+            },
+            quote! {},
+        ),
+    };
+
+    let write = quote! {
+        use std::io::Write;
+        #write_payload
+    };
+    let read = quote! {
+        let __tag = <#tag as ::binary_io::BinPack>::from_bytes_endian(__reader, __endian) #tag_eof?;
+        let mut __padding = [0u8; #tag_padding];
+        ::binary_io::read_exact(__reader, &mut __padding)
+            .map_err(::binary_io::BinaryIoError::within_record)?;
+        #read_payload
+        #(#read_arms)*
+        Err(::binary_io::BinaryIoError::InvalidDiscriminant {
+            type_name: stringify!(#name),
+            value: __tag as i128,
+        })
+    };
+
+    let mut regions = vec![Region {
+        name: "(tag)".to_string(),
+        type_name: {
+            let tag = type_name(tag);
+            quote! { #tag }
+        },
+        size: quote! { Some(std::mem::size_of::<#tag>()) },
+    }];
+    if container.payload_align.is_some() {
+        regions.push(Region {
+            name: "(padding)".to_string(),
+            type_name: quote! { "padding" },
+            size: quote! { Some(#tag_padding) },
+        });
     }
-    return false;
+    let variants = data
+        .variants
+        .iter()
+        .map(|v| v.ident.to_string())
+        .collect::<Vec<_>>()
+        .join(" | ");
+    regions.push(Region {
+        name: "(payload)".to_string(),
+        type_name: quote! { #variants },
+        size: match (&container.payload_size, variant_size) {
+            (Some(size), _) => quote! { Some(#size) },
+            (None, Some(size)) => size,
+            (None, None) => quote! { None },
+        },
+    });
+    Ok(Generated {
+        to_bytes: write,
+        from_bytes: read,
+        regions,
+    })
 }
 
-#[proc_macro_derive(BinaryIO)]
-pub fn binary_io_derive(input: TokenStream) -> TokenStream { 
+#[proc_macro_derive(BinaryIO, attributes(binary_io))]
+pub fn binary_io_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-     // Build the output, possibly using quasi-quotation
+    // Build the output, possibly using quasi-quotation
     let name = &input.ident;
-    let is_repr_c = input.attrs.iter().find(|a| check_if_attr_is_repr_c(a)).is_some();
-    if !is_repr_c {
-        let error = quote_spanned!{
-            name.span() => compile_error!("Struct must be repr(C)");
-        };
-        return TokenStream::from(error);
+    let container = match parse_container_attrs(&input.attrs) {
+        Ok(container) => container,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let generated = match &input.data {
+        Data::Struct(data)
+            if container.tag.is_none()
+                && container.payload_align.is_none()
+                && container.payload_size.is_none() =>
+        {
+            gen_struct(name, &data.fields, &container)
+        }
+        Data::Struct(_) => Err(syn::Error::new_spanned(
+            name,
+            "tag, payload_align and payload_size are only valid on enums",
+        )),
+        Data::Enum(data) => gen_enum(name, data, &container),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            name,
+            "BinaryIO cannot be derived for unions, use an enum with a tag instead",
+        )),
+    };
+    let Generated {
+        to_bytes,
+        from_bytes,
+        mut regions,
+    } = match generated {
+        Ok(generated) => generated,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // a versioned body is written behind its version and length, so that readers
+    // of other versions know what they are looking at and where the record ends
+    let (to_bytes, from_bytes) = match (&container.version, &container.migrate) {
+        (Some(version), migrate) => {
+            let version_eof = container.magic.is_none();
+            let migrate = migrate.as_ref().map(|migrate| {
+                quote! {
+                    if __version < (#version) as u16 {
+                        if let Some(__migrated) = #migrate(__version, &__body, __endian)? {
+                            return Ok(__migrated);
+                        }
+                    }
+                }
+            });
+            regions.splice(
+                0..0,
+                [
+                    Region {
+                        name: "(version)".to_string(),
+                        type_name: quote! { "u16" },
+                        size: quote! { Some(2) },
+                    },
+                    Region {
+                        name: "(length)".to_string(),
+                        type_name: quote! { "u32" },
+                        size: quote! { Some(4) },
+                    },
+                ],
+            );
+            (
+                quote! {
+                    let __header_writer = __writer;
+                    let mut __body = Vec::new();
+                    {
+                        let __writer = &mut __body;
+                        #to_bytes
+                    }
+                    ::binary_io::__private::write_versioned(
+                        __header_writer,
+                        (#version) as u16,
+                        &__body,
+                        __endian,
+                    )?;
+                },
+                quote! {
+                    let (__version, __body) =
+                        ::binary_io::__private::read_versioned(__reader, __endian, #version_eof)?;
+                    #migrate
+                    let __reader = &mut &__body[..];
+                    #from_bytes
+                },
+            )
+        }
+        (None, Some(migrate)) => {
+            return syn::Error::new_spanned(migrate, "migrate needs a version, e.g. #[binary_io(version = 2)]")
+                .to_compile_error()
+                .into()
+        }
+        (None, None) => (to_bytes, from_bytes),
+    };
+    if let Some(magic) = &container.magic {
+        regions.insert(0, magic_region(magic));
     }
-    let expanded = quote! {
-        impl BinPack for #name {
-            fn from_bytes<T: std::io::BufRead + std::io::Seek>(reader: &mut T) -> Option<Self> {
-                let mut buf = [0u8; std::mem::size_of::<Self>()];
-                match reader.read_exact(&mut buf) {
-                    Ok(_) => unsafe { Some(std::mem::transmute(buf)) },
-                    Err(_) => None,
+    let (schema, static_size) = gen_schema(&regions);
+    let (static_size, size_check) = match &container.size {
+        Some(size) => (
+            quote! {
+                match #static_size {
+                    Some(__size) if __size == #size => Some(__size),
+                    _ => panic!(concat!(
+                        stringify!(#name),
+                        " does not encode to ",
+                        stringify!(#size),
+                        " bytes"
+                    )),
+                }
+            },
+            // generic types are checked for each instantiation, when STATIC_SIZE gets used
+            if input.generics.params.is_empty() {
+                quote! {
+                    const _: Option<usize> = <#name as ::binary_io::BinPack>::STATIC_SIZE;
+                }
+            } else {
+                quote! {}
+            },
+        ),
+        None => (static_size, quote! {}),
+    };
+    let (write_magic, read_magic) = match &container.magic {
+        Some(magic) => (
+            quote! { std::io::Write::write_all(__writer, #magic)?; },
+            quote! { ::binary_io::__private::expect_magic(__reader, #magic)?; },
+        ),
+        None => (quote! {}, quote! {}),
+    };
+    // a pinned byte order is part of the format, so it wins over whatever the caller asks for
+    let (endian_const, endian_override) = match &container.endian {
+        Some(endian) => (
+            quote! { const ENDIAN: ::binary_io::Endian = #endian; },
+            quote! { let __endian = #endian; },
+        ),
+        None => (quote! {}, quote! {}),
+    };
+    let generics = add_trait_bounds(input.generics.clone());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // only types that state their size are FixedSize, so that the bound rejects the others
+    let fixed_size = container.size.as_ref().map(|_| {
+        quote! {
+            impl #impl_generics ::binary_io::FixedSize for #name #ty_generics #where_clause {
+                // STATIC_SIZE fails to compile if it is not `size`
+                const ENCODED_SIZE: usize = match <Self as ::binary_io::BinPack>::STATIC_SIZE {
+                    Some(size) => size,
+                    None => unreachable!(),
+                };
+            }
+        }
+    });
+    // async reads need the length of a record before decoding it, from its size or version header
+    let record_len = match (&container.size, &container.version) {
+        (Some(_), _) => Some(quote! {
+            ::binary_io::RecordLen::Fixed(<Self as ::binary_io::FixedSize>::ENCODED_SIZE)
+        }),
+        (None, Some(_)) => {
+            let offset = container.magic.as_ref().map_or(0, |m| m.value().len());
+            let endian = match &container.endian {
+                Some(endian) => quote! { Some(#endian) },
+                None => quote! { None },
+            };
+            Some(quote! {
+                ::binary_io::RecordLen::Versioned { offset: #offset, endian: #endian }
+            })
+        }
+        (None, None) => None,
+    };
+    let async_impl = record_len.map(|record_len| {
+        quote! {
+            ::binary_io::__impl_async! {
+                impl #impl_generics ::binary_io::AsyncBinPack for #name #ty_generics #where_clause {
+                    const RECORD_LEN: ::binary_io::RecordLen = #record_len;
                 }
             }
-            fn to_bytes<T: std::io::Write>(
+        }
+    });
+    let expanded = quote! {
+        impl #impl_generics ::binary_io::BinPack for #name #ty_generics #where_clause {
+            #endian_const
+            const STATIC_SIZE: Option<usize> = #static_size;
+            const SCHEMA: &'static [::binary_io::FieldInfo] = &[#(#schema),*];
+            #[allow(unused_variables)]
+            fn from_bytes_endian<__R: std::io::Read + ?Sized>(
+                __reader: &mut __R,
+                __endian: ::binary_io::Endian,
+            ) -> Result<Self, ::binary_io::BinaryIoError> {
+                #endian_override
+                #read_magic
+                #from_bytes
+            }
+            #[allow(unused_variables)]
+            fn to_bytes_endian<__W: std::io::Write + ?Sized>(
                 &self,
-                writer: &mut std::io::BufWriter<T>,
+                __writer: &mut __W,
+                __endian: ::binary_io::Endian,
             ) -> Result<(), std::io::Error> {
-                let buf: [u8; std::mem::size_of::<Self>()] = unsafe { std::mem::transmute(*self) };
-                writer.write_all(&buf)?;
+                #endian_override
+                #write_magic
+                #to_bytes
                 Ok(())
             }
         }
+
+        #fixed_size
+        #size_check
+        #async_impl
     };
 
     // Hand the output tokens back to the compiler
     TokenStream::from(expanded)
-}
\ No newline at end of file
+}
@@ -0,0 +1,104 @@
+use crate::{BinPack, BinaryIoError, Endian, FixedSize};
+use std::future::Future;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// how many bytes of the stream a record takes, known before it is decoded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordLen {
+    // every record takes the same number of bytes
+    Fixed(usize),
+    // a version header comes after `offset` bytes of magic and gives the length of the body,
+    // in the byte order the type pins if it does
+    Versioned {
+        offset: usize,
+        endian: Option<Endian>,
+    },
+}
+
+// async counterpart of `from_bytes`/`to_bytes`. a record is encoded in memory and written in one
+// go, and read in one go as well before being decoded, so the reader never takes more bytes from
+// the stream than the record needs. the derive implements it for types with a `size` or a `version`
+pub trait AsyncBinPack: BinPack + Sized {
+    const RECORD_LEN: RecordLen;
+
+    fn read_from<R: AsyncRead + Unpin + Send + ?Sized>(
+        reader: &mut R,
+    ) -> impl Future<Output = Result<Self, BinaryIoError>> + Send {
+        Self::read_from_endian(reader, Self::ENDIAN)
+    }
+    fn read_from_endian<R: AsyncRead + Unpin + Send + ?Sized>(
+        reader: &mut R,
+        endian: Endian,
+    ) -> impl Future<Output = Result<Self, BinaryIoError>> + Send {
+        read_record(reader, endian)
+    }
+    fn write_to<W: AsyncWrite + Unpin + Send + ?Sized>(
+        &self,
+        writer: &mut W,
+    ) -> impl Future<Output = Result<(), std::io::Error>> + Send {
+        self.write_to_endian(writer, Self::ENDIAN)
+    }
+    fn write_to_endian<W: AsyncWrite + Unpin + Send + ?Sized>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+    ) -> impl Future<Output = Result<(), std::io::Error>> + Send {
+        let bytes = self.to_vec_endian(endian);
+        async move { writer.write_all(&bytes?).await }
+    }
+}
+
+macro_rules! impl_async_for_fixed {
+    ($($t:ty),*) => {
+        $(
+            impl AsyncBinPack for $t {
+                const RECORD_LEN: RecordLen = RecordLen::Fixed(<$t as FixedSize>::ENCODED_SIZE);
+            }
+        )*
+    };
+}
+
+impl_async_for_fixed!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool);
+
+impl<U: FixedSize, const N: usize> AsyncBinPack for [U; N] {
+    const RECORD_LEN: RecordLen = RecordLen::Fixed(U::ENCODED_SIZE * N);
+}
+
+// appends `len` bytes of the stream to `buf`. only a stream ending before the record started is
+// a clean EOF
+async fn read_more<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    len: usize,
+) -> Result<(), BinaryIoError> {
+    let start = buf.len();
+    // grows with the data actually read instead of trusting a length from the stream
+    reader.take(len as u64).read_to_end(buf).await?;
+    match buf.len() - start {
+        read if read == len => Ok(()),
+        0 if start == 0 => Err(BinaryIoError::Eof),
+        _ => Err(BinaryIoError::Truncated),
+    }
+}
+
+async fn read_record<T: AsyncBinPack, R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    endian: Endian,
+) -> Result<T, BinaryIoError> {
+    let mut buf = vec![];
+    match T::RECORD_LEN {
+        RecordLen::Fixed(len) => read_more(reader, &mut buf, len).await?,
+        RecordLen::Versioned {
+            offset,
+            endian: pinned,
+        } => {
+            // magic, version and length
+            read_more(reader, &mut buf, offset + 6).await?;
+            let (len, _) = u32::from_slice_endian(&buf[offset + 2..], pinned.unwrap_or(endian))?;
+            let len = usize::try_from(len).map_err(|_| BinaryIoError::LengthOverflow)?;
+            read_more(reader, &mut buf, len).await?;
+        }
+    }
+    let (value, _) = T::from_slice_endian(&buf, endian)?;
+    Ok(value)
+}
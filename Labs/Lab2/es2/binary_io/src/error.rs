@@ -0,0 +1,139 @@
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum BinaryIoError {
+    // the reader was exhausted exactly on a record boundary
+    Eof,
+    // the reader ended in the middle of a record
+    Truncated,
+    IoError(std::io::Error),
+    InvalidDiscriminant {
+        type_name: &'static str,
+        value: i128,
+    },
+    InvalidUtf8(std::string::FromUtf8Error),
+    // a length prefix that cannot be represented on this platform
+    LengthOverflow,
+    BadMagic {
+        expected: &'static [u8],
+        found: Vec<u8>,
+    },
+    AssertionFailed {
+        field: &'static str,
+        condition: &'static str,
+    },
+    // a frame whose CRC32 does not match its contents, `offset` is where the frame starts
+    ChecksumMismatch {
+        offset: u64,
+        expected: u32,
+        found: u32,
+    },
+    // a frame header announcing more bytes than the reader accepts, most likely a corrupted length
+    FrameTooLong {
+        offset: u64,
+        len: usize,
+    },
+    OutOfBounds {
+        index: usize,
+        len: usize,
+    },
+    // a region that should hold fixed size records but does not end on a record boundary
+    Misaligned {
+        len: u64,
+        record_size: usize,
+    },
+}
+
+impl BinaryIoError {
+    // once part of a record has been consumed, running out of input is no longer a clean EOF
+    pub fn within_record(self) -> Self {
+        match self {
+            BinaryIoError::Eof => BinaryIoError::Truncated,
+            e => e,
+        }
+    }
+    pub fn is_eof(&self) -> bool {
+        matches!(self, BinaryIoError::Eof)
+    }
+}
+
+impl Display for BinaryIoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryIoError::Eof => write!(f, "End of file"),
+            BinaryIoError::Truncated => write!(f, "Truncated record"),
+            BinaryIoError::IoError(e) => write!(f, "I/O error: {}", e),
+            BinaryIoError::InvalidDiscriminant { type_name, value } => {
+                write!(f, "Invalid discriminant {} for {}", value, type_name)
+            }
+            BinaryIoError::InvalidUtf8(e) => write!(f, "Invalid UTF-8 string: {}", e),
+            BinaryIoError::LengthOverflow => write!(f, "Length prefix overflows"),
+            BinaryIoError::BadMagic { expected, found } => write!(
+                f,
+                "Bad magic number: expected {:02x?}, found {:02x?}",
+                expected, found
+            ),
+            BinaryIoError::AssertionFailed { field, condition } => {
+                write!(
+                    f,
+                    "Assertion `{}` failed after reading {}",
+                    condition, field
+                )
+            }
+            BinaryIoError::ChecksumMismatch {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Checksum mismatch in frame at offset {}: expected {:08x}, found {:08x}",
+                offset, expected, found
+            ),
+            BinaryIoError::FrameTooLong { offset, len } => {
+                write!(f, "Frame at offset {} is too long ({} bytes)", offset, len)
+            }
+            BinaryIoError::OutOfBounds { index, len } => {
+                write!(f, "Index {} out of bounds for {} records", index, len)
+            }
+            BinaryIoError::Misaligned { len, record_size } => write!(
+                f,
+                "{} bytes are not a whole number of {} byte records",
+                len, record_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BinaryIoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BinaryIoError::IoError(e) => Some(e),
+            BinaryIoError::InvalidUtf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BinaryIoError {
+    fn from(e: std::io::Error) -> Self {
+        BinaryIoError::IoError(e)
+    }
+}
+
+// like `Read::read_exact`, but tells apart a reader that was already empty from one that ran dry halfway
+pub fn read_exact<R: std::io::Read + ?Sized>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> Result<(), BinaryIoError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Err(BinaryIoError::Eof),
+            Ok(0) => return Err(BinaryIoError::Truncated),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::{BinPack, BinaryIoError};
+use std::io::{Read, Write};
+
+// every frame is `len: u32 | crc32: u32 | payload` in little endian. the checksum covers the
+// length as well, so a corrupted length is caught instead of sending the reader astray
+const HEADER_SIZE: usize = 8;
+pub const DEFAULT_MAX_FRAME_LEN: usize = 1 << 20;
+
+fn checksum(len: [u8; 4], payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&len);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+pub struct FramedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> FramedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        FramedWriter { inner }
+    }
+
+    pub fn write<T: BinPack>(&mut self, value: &T) -> Result<(), std::io::Error> {
+        self.write_frame(&value.to_vec()?)
+    }
+
+    pub fn write_frame(&mut self, payload: &[u8]) -> Result<(), std::io::Error> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("payload of {} bytes does not fit in a frame", payload.len()),
+                )
+            })?
+            .to_le_bytes();
+        // a single write per frame, so that a crash tears at most this frame
+        let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&len);
+        frame.extend_from_slice(&checksum(len, payload).to_le_bytes());
+        frame.extend_from_slice(payload);
+        self.inner.write_all(&frame)
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+// reads frames written by `FramedWriter`. bytes are buffered until a frame is known to be good,
+// so after an error `resync` can look for the next valid frame right after the bad one
+pub struct FramedReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    offset: u64,
+    max_frame_len: usize,
+}
+
+impl<R: Read> FramedReader<R> {
+    pub fn new(inner: R) -> Self {
+        FramedReader {
+            inner,
+            buf: vec![],
+            offset: 0,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+
+    // longer frames are reported as corrupted instead of being buffered
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    // offset of the next frame from where the reader started
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn read<T: BinPack>(&mut self) -> Result<T, BinaryIoError> {
+        let payload = self.read_frame()?;
+        let (value, _) = T::from_slice(&payload).map_err(BinaryIoError::within_record)?;
+        Ok(value)
+    }
+
+    pub fn read_frame(&mut self) -> Result<Vec<u8>, BinaryIoError> {
+        let len = self.check_frame()?;
+        let payload = self.buf[HEADER_SIZE..HEADER_SIZE + len].to_vec();
+        self.consume(HEADER_SIZE + len);
+        Ok(payload)
+    }
+
+    // drops bytes until a valid frame starts at the current position and returns how many were
+    // dropped. a torn frame at the end of the data is dropped as well, leaving the reader at EOF
+    pub fn resync(&mut self) -> Result<u64, BinaryIoError> {
+        let mut skipped = 0;
+        loop {
+            match self.check_frame() {
+                Ok(_) | Err(BinaryIoError::Eof) => return Ok(skipped),
+                Err(
+                    BinaryIoError::Truncated
+                    | BinaryIoError::ChecksumMismatch { .. }
+                    | BinaryIoError::FrameTooLong { .. },
+                ) => {
+                    self.consume(1);
+                    skipped += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // makes sure `n` bytes are buffered, false if the reader ends first
+    fn fill(&mut self, n: usize) -> Result<bool, std::io::Error> {
+        let mut chunk = [0u8; 4096];
+        while self.buf.len() < n {
+            match self.inner.read(&mut chunk) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.buf.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.offset += n as u64;
+    }
+
+    // validates the frame at the current position without consuming it, returns its payload length
+    fn check_frame(&mut self) -> Result<usize, BinaryIoError> {
+        if !self.fill(HEADER_SIZE)? {
+            return Err(if self.buf.is_empty() {
+                BinaryIoError::Eof
+            } else {
+                BinaryIoError::Truncated
+            });
+        }
+        let len_bytes = [self.buf[0], self.buf[1], self.buf[2], self.buf[3]];
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > self.max_frame_len {
+            return Err(BinaryIoError::FrameTooLong {
+                offset: self.offset,
+                len,
+            });
+        }
+        if !self.fill(HEADER_SIZE + len)? {
+            return Err(BinaryIoError::Truncated);
+        }
+        let expected = u32::from_le_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]]);
+        let found = checksum(len_bytes, &self.buf[HEADER_SIZE..HEADER_SIZE + len]);
+        if expected != found {
+            return Err(BinaryIoError::ChecksumMismatch {
+                offset: self.offset,
+                expected,
+                found,
+            });
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{BinPack, BinaryIoError, Endian, FieldInfo};
+use std::fmt::Debug;
+use std::io::Write;
+
+type Decoder = fn(&[u8], Option<Endian>) -> Result<(Box<dyn Debug>, usize), BinaryIoError>;
+
+fn decode_as<T: BinPack + Debug + 'static>(
+    bytes: &[u8],
+    endian: Option<Endian>,
+) -> Result<(Box<dyn Debug>, usize), BinaryIoError> {
+    let (value, rest) = T::from_slice_endian(bytes, endian.unwrap_or(T::ENDIAN))?;
+    Ok((Box::new(value), bytes.len() - rest.len()))
+}
+
+// a record type known to the inspector, decoded through its BinPack impl
+pub struct Schema {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub fields: &'static [FieldInfo],
+    pub size: Option<usize>,
+    decode: Decoder,
+}
+
+impl Schema {
+    pub fn of<T: BinPack + Debug + 'static>(name: &'static str) -> Self {
+        Schema {
+            name,
+            type_name: std::any::type_name::<T>(),
+            fields: T::SCHEMA,
+            size: T::STATIC_SIZE,
+            decode: decode_as::<T>,
+        }
+    }
+
+    // decodes one record from the front of `bytes`, returning it with the number of bytes it used
+    pub fn decode(
+        &self,
+        bytes: &[u8],
+        endian: Option<Endian>,
+    ) -> Result<(Box<dyn Debug>, usize), BinaryIoError> {
+        (self.decode)(bytes, endian)
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    schemas: Vec<Schema>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // registering a name twice replaces the previous schema
+    pub fn register<T: BinPack + Debug + 'static>(&mut self, name: &'static str) -> &mut Self {
+        self.schemas.retain(|s| s.name != name);
+        self.schemas.push(Schema::of::<T>(name));
+        self
+    }
+
+    // adds the schemas of another registry, which replace those of the same name
+    pub fn merge(&mut self, other: Registry) -> &mut Self {
+        for schema in other.schemas {
+            self.schemas.retain(|s| s.name != schema.name);
+            self.schemas.push(schema);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Schema> {
+        self.schemas.iter().find(|s| s.name == name)
+    }
+
+    pub fn schemas(&self) -> impl Iterator<Item = &Schema> {
+        self.schemas.iter()
+    }
+}
+
+// how the inspected data is laid out: an optional header followed by records
+pub struct Layout<'a> {
+    pub header: Option<&'a Schema>,
+    pub record: &'a Schema,
+    // number of records expected, whatever follows them is reported as trailing
+    pub count: Option<usize>,
+    // None decodes every schema in its own byte order
+    pub endian: Option<Endian>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub records: usize,
+    pub errors: usize,
+    // offset and length of a record cut short by the end of the data
+    pub truncated: Option<(usize, usize)>,
+    // offset and length of the bytes left over after the last record
+    pub trailing: Option<(usize, usize)>,
+}
+
+enum Outcome {
+    Decoded(usize),
+    Truncated,
+    Invalid,
+}
+
+const HEX_BYTES: usize = 16;
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = bytes
+        .iter()
+        .take(HEX_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > HEX_BYTES {
+        out.push_str(" ...");
+    }
+    out
+}
+
+fn dump_record<W: Write + ?Sized>(
+    out: &mut W,
+    bytes: &[u8],
+    offset: usize,
+    schema: &Schema,
+    label: &str,
+    endian: Option<Endian>,
+) -> std::io::Result<Outcome> {
+    let data = &bytes[offset..];
+    let (value, len) = match schema.decode(data, endian) {
+        Ok(decoded) => decoded,
+        Err(BinaryIoError::Eof | BinaryIoError::Truncated) => {
+            writeln!(
+                out,
+                "{:#010x}  {} {}: truncated, only {} bytes left",
+                offset,
+                label,
+                schema.name,
+                data.len()
+            )?;
+            return Ok(Outcome::Truncated);
+        }
+        Err(e) => {
+            writeln!(out, "{:#010x}  {} {}: {}", offset, label, schema.name, e)?;
+            return Ok(Outcome::Invalid);
+        }
+    };
+    writeln!(out, "{:#010x}  {} {} ({} bytes)", offset, label, schema.name, len)?;
+    let name_width = schema.fields.iter().map(|f| f.name.len()).max().unwrap_or(0);
+    let type_width = schema.fields.iter().map(|f| f.type_name.len()).max().unwrap_or(0);
+    for field in schema.fields {
+        let at = match field.offset {
+            Some(at) => format!("+{:#06x}", at),
+            None => "+?".to_string(),
+        };
+        let contents = match (field.offset, field.size) {
+            (Some(at), Some(size)) => data.get(at..at + size).map(hex).unwrap_or_default(),
+            _ => "(variable size)".to_string(),
+        };
+        writeln!(
+            out,
+            "    {:<8} {:<name_width$}  {:<type_width$}  {}",
+            at, field.name, field.type_name, contents
+        )?;
+    }
+    writeln!(out, "    = {:?}", value)?;
+    Ok(Outcome::Decoded(len))
+}
+
+// pretty-prints every record in `bytes` with its offset, flagging records that are cut short
+// and bytes that do not belong to any record
+pub fn inspect<W: Write + ?Sized>(
+    out: &mut W,
+    bytes: &[u8],
+    layout: &Layout,
+) -> std::io::Result<Report> {
+    let mut report = Report::default();
+    let mut offset = 0;
+    let slots = layout
+        .header
+        .map(|h| (h, None))
+        .into_iter()
+        .chain((0..).map(|i| (layout.record, Some(i))));
+    let limit = match layout.count {
+        Some(count) => count + layout.header.is_some() as usize,
+        None => usize::MAX,
+    };
+    for (schema, index) in slots.take(limit) {
+        if offset == bytes.len() {
+            break;
+        }
+        let label = match index {
+            Some(i) => format!("#{}", i),
+            None => "header".to_string(),
+        };
+        match dump_record(out, bytes, offset, schema, &label, layout.endian)? {
+            Outcome::Decoded(len) => {
+                offset += len;
+                if index.is_some() {
+                    report.records += 1;
+                }
+                // a record that takes no space would never reach the end of the data
+                if len == 0 && layout.count.is_none() {
+                    break;
+                }
+            }
+            Outcome::Truncated => {
+                report.truncated = Some((offset, bytes.len() - offset));
+                offset = bytes.len();
+                break;
+            }
+            // records of a fixed size can be skipped, otherwise there is no telling where the next one starts
+            Outcome::Invalid => {
+                report.errors += 1;
+                match schema.size {
+                    Some(size) if offset + size <= bytes.len() => offset += size,
+                    _ => break,
+                }
+            }
+        }
+    }
+    if offset < bytes.len() {
+        writeln!(
+            out,
+            "{:#010x}  {} trailing bytes",
+            offset,
+            bytes.len() - offset
+        )?;
+        report.trailing = Some((offset, bytes.len() - offset));
+    }
+    Ok(report)
+}
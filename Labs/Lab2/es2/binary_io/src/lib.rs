@@ -1,12 +1,350 @@
+#[cfg(feature = "async")]
+mod async_io;
+mod error;
+mod framing;
+pub mod inspect;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod prefixed;
+
 pub use binary_io_derive::BinaryIO;
 
-pub use std::io::{BufRead, Write, BufReader, BufWriter, Seek};
+// support code for the derive, not part of the public API
+#[doc(hidden)]
+pub mod __private {
+    use crate::{read_exact, BinPack, BinaryIoError, Endian};
+    use std::io::Read;
+
+    pub fn skip<R: std::io::Read + ?Sized>(
+        reader: &mut R,
+        len: usize,
+    ) -> Result<(), BinaryIoError> {
+        read_exact(reader, &mut vec![0u8; len])
+    }
+
+    pub fn expect_magic<R: std::io::Read + ?Sized>(
+        reader: &mut R,
+        expected: &'static [u8],
+    ) -> Result<(), BinaryIoError> {
+        let mut found = vec![0u8; expected.len()];
+        read_exact(reader, &mut found)?;
+        if found != expected {
+            return Err(BinaryIoError::BadMagic { expected, found });
+        }
+        Ok(())
+    }
+
+    pub fn write_versioned<W: std::io::Write + ?Sized>(
+        writer: &mut W,
+        version: u16,
+        body: &[u8],
+        endian: Endian,
+    ) -> std::io::Result<()> {
+        let len = u32::try_from(body.len()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("record of {} bytes is too long for a version header", body.len()),
+            )
+        })?;
+        version.to_bytes_endian(writer, endian)?;
+        len.to_bytes_endian(writer, endian)?;
+        writer.write_all(body)
+    }
+
+    // reads a version header and the body it announces, the body may be longer than this
+    // version of the type knows about, whatever it does not read is simply dropped
+    pub fn read_versioned<R: std::io::Read + ?Sized>(
+        reader: &mut R,
+        endian: Endian,
+        may_eof: bool,
+    ) -> Result<(u16, Vec<u8>), BinaryIoError> {
+        let version = u16::from_bytes_endian(reader, endian);
+        let version = if may_eof {
+            version?
+        } else {
+            version.map_err(BinaryIoError::within_record)?
+        };
+        let len = u32::from_bytes_endian(reader, endian).map_err(BinaryIoError::within_record)?;
+        let len = usize::try_from(len).map_err(|_| BinaryIoError::LengthOverflow)?;
+        // grows with the data actually read instead of trusting the header
+        let mut body = vec![];
+        std::io::Read::take(reader, len as u64).read_to_end(&mut body)?;
+        if body.len() < len {
+            return Err(BinaryIoError::Truncated);
+        }
+        Ok((version, body))
+    }
+
+    // `Option` arithmetic usable in the constants emitted for STATIC_SIZE and SCHEMA
+    pub const fn add(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            _ => None,
+        }
+    }
+
+    pub const fn same(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(a), Some(b)) if a == b => Some(a),
+            _ => None,
+        }
+    }
+}
+#[cfg(feature = "async")]
+pub use async_io::{AsyncBinPack, RecordLen};
+
+// the derive wraps its AsyncBinPack impls in this, so that they go away without the async feature
+#[cfg(feature = "async")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_async {
+    ($($item:tt)*) => { $($item)* };
+}
+#[cfg(not(feature = "async"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_async {
+    ($($item:tt)*) => {};
+}
+pub use error::{read_exact, BinaryIoError};
+pub use framing::{FramedReader, FramedWriter, DEFAULT_MAX_FRAME_LEN};
+#[cfg(feature = "mmap")]
+pub use mapped::MappedRecords;
+pub use prefixed::{LengthPrefix, LengthPrefixed};
+
+pub use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+// one region of an encoded record, as listed in `BinPack::SCHEMA`.
+// offset and size are None when they depend on the data, e.g. after a length-prefixed field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub offset: Option<usize>,
+    pub size: Option<usize>,
+    pub type_name: &'static str,
+}
+
+impl Endian {
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Endian = Endian::Little;
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Endian = Endian::Big;
+}
 
 pub trait BinPack {
-    fn to_bytes<T: std::io::Write>(&self, writer: &mut std::io::BufWriter<T>) -> Result<(), std::io::Error>
+    // byte order used by `to_bytes` and `from_bytes`.
+    // types deriving BinaryIO can pin it with `#[binary_io(endian = "big")]`
+    const ENDIAN: Endian = Endian::NATIVE;
+    // encoded size in bytes, if it is the same for every value of the type
+    const STATIC_SIZE: Option<usize> = None;
+    // the fields of the encoded form in order, filled in by the derive
+    const SCHEMA: &'static [FieldInfo] = &[];
+
+    fn to_bytes_endian<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+    ) -> Result<(), std::io::Error>
     where
         Self: Sized;
-    fn from_bytes<T: std::io::BufRead + std::io::Seek>(reader: &mut T) -> Option<Self>
+    fn from_bytes_endian<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+    ) -> Result<Self, BinaryIoError>
     where
         Self: Sized;
-}
\ No newline at end of file
+
+    fn to_bytes<T: std::io::Write + ?Sized>(&self, writer: &mut T) -> Result<(), std::io::Error>
+    where
+        Self: Sized,
+    {
+        self.to_bytes_endian(writer, Self::ENDIAN)
+    }
+    fn to_bytes_le<T: std::io::Write + ?Sized>(&self, writer: &mut T) -> Result<(), std::io::Error>
+    where
+        Self: Sized,
+    {
+        self.to_bytes_endian(writer, Endian::Little)
+    }
+    fn to_bytes_be<T: std::io::Write + ?Sized>(&self, writer: &mut T) -> Result<(), std::io::Error>
+    where
+        Self: Sized,
+    {
+        self.to_bytes_endian(writer, Endian::Big)
+    }
+    fn from_bytes<T: std::io::Read + ?Sized>(reader: &mut T) -> Result<Self, BinaryIoError>
+    where
+        Self: Sized,
+    {
+        Self::from_bytes_endian(reader, Self::ENDIAN)
+    }
+    fn from_bytes_le<T: std::io::Read + ?Sized>(reader: &mut T) -> Result<Self, BinaryIoError>
+    where
+        Self: Sized,
+    {
+        Self::from_bytes_endian(reader, Endian::Little)
+    }
+    fn from_bytes_be<T: std::io::Read + ?Sized>(reader: &mut T) -> Result<Self, BinaryIoError>
+    where
+        Self: Sized,
+    {
+        Self::from_bytes_endian(reader, Endian::Big)
+    }
+
+    fn to_vec(&self) -> Result<Vec<u8>, std::io::Error>
+    where
+        Self: Sized,
+    {
+        self.to_vec_endian(Self::ENDIAN)
+    }
+    fn to_vec_endian(&self, endian: Endian) -> Result<Vec<u8>, std::io::Error>
+    where
+        Self: Sized,
+    {
+        let mut buf = vec![];
+        self.to_bytes_endian(&mut buf, endian)?;
+        Ok(buf)
+    }
+    // decodes a value from the front of `bytes` and hands back whatever follows it
+    fn from_slice(bytes: &[u8]) -> Result<(Self, &[u8]), BinaryIoError>
+    where
+        Self: Sized,
+    {
+        Self::from_slice_endian(bytes, Self::ENDIAN)
+    }
+    fn from_slice_endian(bytes: &[u8], endian: Endian) -> Result<(Self, &[u8]), BinaryIoError>
+    where
+        Self: Sized,
+    {
+        let mut rest = bytes;
+        let value = Self::from_bytes_endian(&mut rest, endian)?;
+        Ok((value, rest))
+    }
+}
+
+// types whose encoding always takes the same number of bytes, as needed by `MappedRecords`.
+// the derive implements it for types with a `size`, which is checked at compile time
+pub trait FixedSize: BinPack {
+    const ENCODED_SIZE: usize;
+}
+
+macro_rules! impl_binpack_for_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl BinPack for $t {
+                const STATIC_SIZE: Option<usize> = Some(std::mem::size_of::<$t>());
+                fn to_bytes_endian<T: std::io::Write + ?Sized>(
+                    &self,
+                    writer: &mut T,
+                    endian: Endian,
+                ) -> Result<(), std::io::Error> {
+                    match endian {
+                        Endian::Little => writer.write_all(&self.to_le_bytes()),
+                        Endian::Big => writer.write_all(&self.to_be_bytes()),
+                    }
+                }
+                fn from_bytes_endian<T: std::io::Read + ?Sized>(reader: &mut T, endian: Endian) -> Result<Self, BinaryIoError> {
+                    let mut buf = [0u8; std::mem::size_of::<$t>()];
+                    read_exact(reader, &mut buf)?;
+                    match endian {
+                        Endian::Little => Ok(<$t>::from_le_bytes(buf)),
+                        Endian::Big => Ok(<$t>::from_be_bytes(buf)),
+                    }
+                }
+            }
+
+            impl FixedSize for $t {
+                const ENCODED_SIZE: usize = std::mem::size_of::<$t>();
+            }
+        )*
+    };
+}
+
+// lets a `std::hash::Hasher` be used as a sink, e.g. to hash the encoded form of a record
+pub struct HashWriter<H: std::hash::Hasher>(pub H);
+
+impl<H: std::hash::Hasher> std::io::Write for HashWriter<H> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl_binpack_for_numbers!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+impl BinPack for bool {
+    const STATIC_SIZE: Option<usize> = Some(1);
+    fn to_bytes_endian<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        _endian: Endian,
+    ) -> Result<(), std::io::Error> {
+        writer.write_all(&[*self as u8])
+    }
+    fn from_bytes_endian<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+    ) -> Result<Self, BinaryIoError> {
+        // anything other than 0 or 1 is not a valid bool, refuse it instead of guessing
+        match u8::from_bytes_endian(reader, endian)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(BinaryIoError::InvalidDiscriminant {
+                type_name: "bool",
+                value: value.into(),
+            }),
+        }
+    }
+}
+
+impl FixedSize for bool {
+    const ENCODED_SIZE: usize = 1;
+}
+
+impl<U: FixedSize, const N: usize> FixedSize for [U; N] {
+    const ENCODED_SIZE: usize = U::ENCODED_SIZE * N;
+}
+
+impl<U: BinPack, const N: usize> BinPack for [U; N] {
+    const STATIC_SIZE: Option<usize> = match U::STATIC_SIZE {
+        Some(size) => Some(size * N),
+        None => None,
+    };
+    fn to_bytes_endian<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+    ) -> Result<(), std::io::Error> {
+        for v in self {
+            v.to_bytes_endian(writer, endian)?;
+        }
+        Ok(())
+    }
+    fn from_bytes_endian<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+    ) -> Result<Self, BinaryIoError> {
+        let mut values = Vec::with_capacity(N);
+        for i in 0..N {
+            let value = U::from_bytes_endian(reader, endian);
+            values.push(if i == 0 {
+                value?
+            } else {
+                value.map_err(BinaryIoError::within_record)?
+            });
+        }
+        match values.try_into() {
+            Ok(values) => Ok(values),
+            Err(_) => unreachable!("exactly N values were read"),
+        }
+    }
+}
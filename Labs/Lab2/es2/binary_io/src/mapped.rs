@@ -0,0 +1,134 @@
+use crate::{BinPack, BinaryIoError, FixedSize};
+use memmap2::{Mmap, MmapMut};
+use std::fs::File;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+enum Map {
+    ReadOnly(Mmap),
+    ReadWrite(MmapMut),
+}
+
+impl Map {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Map::ReadOnly(map) => map,
+            Map::ReadWrite(map) => map,
+        }
+    }
+}
+
+// fixed size records stored back to back in a memory mapped file, starting at `offset`
+// (e.g. after a header). records are decoded and encoded on access, in the byte order of `T`.
+// the file does not record a byte order: types shared between machines should pin one with
+// `#[binary_io(endian = ...)]`
+pub struct MappedRecords<T> {
+    map: Map,
+    offset: usize,
+    len: usize,
+    _records: PhantomData<fn() -> T>,
+}
+
+impl<T: BinPack + FixedSize> MappedRecords<T> {
+    pub fn open(file: &File, offset: u64) -> Result<Self, BinaryIoError> {
+        // SAFETY: the mapping is only accessed as plain bytes and every record is copied out
+        // before being decoded, someone else writing to the file can only make it decode to garbage
+        let map = unsafe { MmapMut::map_mut(file)? };
+        Self::new(Map::ReadWrite(map), offset)
+    }
+
+    pub fn open_read_only(file: &File, offset: u64) -> Result<Self, BinaryIoError> {
+        // SAFETY: see `open`
+        let map = unsafe { Mmap::map(file)? };
+        Self::new(Map::ReadOnly(map), offset)
+    }
+
+    // the layout is checked once here: the records must fill the rest of the file exactly, and
+    // the first one must decode. that only catches a file written for another type or byte order
+    // if the schema can tell, with a magic or an assert field: plain numbers in the wrong byte
+    // order decode without error
+    fn new(map: Map, offset: u64) -> Result<Self, BinaryIoError> {
+        let total = map.bytes().len() as u64;
+        let data_len = total.checked_sub(offset).ok_or(BinaryIoError::Truncated)?;
+        if T::ENCODED_SIZE == 0 || data_len % T::ENCODED_SIZE as u64 != 0 {
+            return Err(BinaryIoError::Misaligned {
+                len: data_len,
+                record_size: T::ENCODED_SIZE,
+            });
+        }
+        let records = MappedRecords {
+            map,
+            offset: offset as usize,
+            len: (data_len / T::ENCODED_SIZE as u64) as usize,
+            _records: PhantomData,
+        };
+        if !records.is_empty() {
+            records.get(0)?;
+        }
+        Ok(records)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn range(&self, index: usize) -> Result<Range<usize>, BinaryIoError> {
+        if index >= self.len {
+            return Err(BinaryIoError::OutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        let start = self.offset + index * T::ENCODED_SIZE;
+        Ok(start..start + T::ENCODED_SIZE)
+    }
+
+    pub fn get(&self, index: usize) -> Result<T, BinaryIoError> {
+        let range = self.range(index)?;
+        let (value, _) = T::from_slice(&self.map.bytes()[range])?;
+        Ok(value)
+    }
+
+    pub fn set(&mut self, index: usize, value: &T) -> Result<(), BinaryIoError> {
+        let range = self.range(index)?;
+        let bytes = value.to_vec()?;
+        if bytes.len() != T::ENCODED_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "record encoded to {} bytes instead of {}",
+                    bytes.len(),
+                    T::ENCODED_SIZE
+                ),
+            )
+            .into());
+        }
+        match &mut self.map {
+            Map::ReadWrite(map) => {
+                map[range].copy_from_slice(&bytes);
+                Ok(())
+            }
+            Map::ReadOnly(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "records were mapped read only",
+            )
+            .into()),
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Result<T, BinaryIoError>> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    // writes modified records back to the file
+    pub fn flush(&self) -> Result<(), std::io::Error> {
+        match &self.map {
+            Map::ReadWrite(map) => map.flush(),
+            Map::ReadOnly(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{read_exact, BinPack, BinaryIoError, Endian};
+
+// how the number of elements of a variable-length value is stored in front of it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    #[default]
+    U32,
+    // unsigned LEB128, 7 bits per byte
+    Varint,
+}
+
+fn too_long(len: usize, prefix: LengthPrefix) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("length {} does not fit in a {:?} prefix", len, prefix),
+    )
+}
+
+impl LengthPrefix {
+    pub fn write<T: std::io::Write + ?Sized>(
+        self,
+        writer: &mut T,
+        len: usize,
+        endian: Endian,
+    ) -> Result<(), std::io::Error> {
+        match self {
+            LengthPrefix::U8 => u8::try_from(len)
+                .map_err(|_| too_long(len, self))?
+                .to_bytes_endian(writer, endian),
+            LengthPrefix::U16 => u16::try_from(len)
+                .map_err(|_| too_long(len, self))?
+                .to_bytes_endian(writer, endian),
+            LengthPrefix::U32 => u32::try_from(len)
+                .map_err(|_| too_long(len, self))?
+                .to_bytes_endian(writer, endian),
+            LengthPrefix::Varint => {
+                let mut value = len as u64;
+                loop {
+                    let byte = (value & 0x7f) as u8;
+                    value >>= 7;
+                    if value == 0 {
+                        return writer.write_all(&[byte]);
+                    }
+                    writer.write_all(&[byte | 0x80])?;
+                }
+            }
+        }
+    }
+
+    pub fn read<T: std::io::Read + ?Sized>(
+        self,
+        reader: &mut T,
+        endian: Endian,
+    ) -> Result<usize, BinaryIoError> {
+        let len = match self {
+            LengthPrefix::U8 => u8::from_bytes_endian(reader, endian)? as u64,
+            LengthPrefix::U16 => u16::from_bytes_endian(reader, endian)? as u64,
+            LengthPrefix::U32 => u32::from_bytes_endian(reader, endian)? as u64,
+            LengthPrefix::Varint => {
+                let mut value = 0u64;
+                let mut shift = 0;
+                loop {
+                    let mut byte = [0u8];
+                    let read = read_exact(reader, &mut byte);
+                    if shift > 0 {
+                        read.map_err(BinaryIoError::within_record)?;
+                    } else {
+                        read?;
+                    }
+                    if shift == 63 && byte[0] > 1 {
+                        return Err(BinaryIoError::LengthOverflow);
+                    }
+                    value |= ((byte[0] & 0x7f) as u64) << shift;
+                    if byte[0] & 0x80 == 0 {
+                        break value;
+                    }
+                    shift += 7;
+                }
+            }
+        };
+        usize::try_from(len).map_err(|_| BinaryIoError::LengthOverflow)
+    }
+}
+
+// variable-length values whose length prefix can be chosen by the caller,
+// plain `BinPack` uses the default u32 prefix.
+pub trait LengthPrefixed: BinPack {
+    fn to_bytes_prefixed<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<(), std::io::Error>
+    where
+        Self: Sized;
+    fn from_bytes_prefixed<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<Self, BinaryIoError>
+    where
+        Self: Sized;
+}
+
+// never trust a length read from disk for the initial allocation
+const MAX_PREALLOCATION: usize = 4096;
+
+fn write_elements<U: BinPack, T: std::io::Write + ?Sized>(
+    values: &[U],
+    writer: &mut T,
+    endian: Endian,
+    prefix: LengthPrefix,
+) -> Result<(), std::io::Error> {
+    prefix.write(writer, values.len(), endian)?;
+    for v in values {
+        v.to_bytes_endian(writer, endian)?;
+    }
+    Ok(())
+}
+
+fn read_elements<U: BinPack, T: std::io::Read + ?Sized>(
+    reader: &mut T,
+    endian: Endian,
+    prefix: LengthPrefix,
+) -> Result<Vec<U>, BinaryIoError> {
+    let len = prefix.read(reader, endian)?;
+    let mut values = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    for _ in 0..len {
+        values.push(U::from_bytes_endian(reader, endian).map_err(BinaryIoError::within_record)?);
+    }
+    Ok(values)
+}
+
+impl<U: BinPack> LengthPrefixed for Vec<U> {
+    fn to_bytes_prefixed<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<(), std::io::Error> {
+        write_elements(self, writer, endian, prefix)
+    }
+    fn from_bytes_prefixed<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<Self, BinaryIoError> {
+        read_elements(reader, endian, prefix)
+    }
+}
+
+impl<U: BinPack> LengthPrefixed for Box<[U]> {
+    fn to_bytes_prefixed<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<(), std::io::Error> {
+        write_elements(self, writer, endian, prefix)
+    }
+    fn from_bytes_prefixed<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<Self, BinaryIoError> {
+        Ok(read_elements(reader, endian, prefix)?.into_boxed_slice())
+    }
+}
+
+// strings are stored as their UTF-8 bytes, the prefix counts bytes and not chars
+impl LengthPrefixed for String {
+    fn to_bytes_prefixed<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<(), std::io::Error> {
+        prefix.write(writer, self.len(), endian)?;
+        writer.write_all(self.as_bytes())
+    }
+    fn from_bytes_prefixed<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<Self, BinaryIoError> {
+        let len = prefix.read(reader, endian)?;
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(
+            &mut std::io::Read::take(&mut *reader, len as u64),
+            &mut bytes,
+        )?;
+        if bytes.len() < len {
+            return Err(BinaryIoError::Truncated);
+        }
+        String::from_utf8(bytes).map_err(BinaryIoError::InvalidUtf8)
+    }
+}
+
+// an Option is a presence byte followed by the value, the prefix applies to the value
+impl<U: LengthPrefixed> LengthPrefixed for Option<U> {
+    fn to_bytes_prefixed<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<(), std::io::Error> {
+        self.is_some().to_bytes_endian(writer, endian)?;
+        match self {
+            Some(v) => v.to_bytes_prefixed(writer, endian, prefix),
+            None => Ok(()),
+        }
+    }
+    fn from_bytes_prefixed<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+        prefix: LengthPrefix,
+    ) -> Result<Self, BinaryIoError> {
+        if bool::from_bytes_endian(reader, endian)? {
+            Ok(Some(
+                U::from_bytes_prefixed(reader, endian, prefix)
+                    .map_err(BinaryIoError::within_record)?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+macro_rules! impl_binpack_with_default_prefix {
+    ($(impl<$($g:ident),*> for $t:ty;)*) => {
+        $(
+            impl<$($g: BinPack),*> BinPack for $t {
+                fn to_bytes_endian<T: std::io::Write + ?Sized>(
+                    &self,
+                    writer: &mut T,
+                    endian: Endian,
+                ) -> Result<(), std::io::Error> {
+                    self.to_bytes_prefixed(writer, endian, LengthPrefix::default())
+                }
+                fn from_bytes_endian<T: std::io::Read + ?Sized>(
+                    reader: &mut T,
+                    endian: Endian,
+                ) -> Result<Self, BinaryIoError> {
+                    Self::from_bytes_prefixed(reader, endian, LengthPrefix::default())
+                }
+            }
+        )*
+    };
+}
+
+impl_binpack_with_default_prefix! {
+    impl<U> for Vec<U>;
+    impl<U> for Box<[U]>;
+    impl<> for String;
+}
+
+impl<U: BinPack> BinPack for Option<U> {
+    fn to_bytes_endian<T: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut T,
+        endian: Endian,
+    ) -> Result<(), std::io::Error> {
+        self.is_some().to_bytes_endian(writer, endian)?;
+        match self {
+            Some(v) => v.to_bytes_endian(writer, endian),
+            None => Ok(()),
+        }
+    }
+    fn from_bytes_endian<T: std::io::Read + ?Sized>(
+        reader: &mut T,
+        endian: Endian,
+    ) -> Result<Self, BinaryIoError> {
+        if bool::from_bytes_endian(reader, endian)? {
+            Ok(Some(
+                U::from_bytes_endian(reader, endian).map_err(BinaryIoError::within_record)?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "async")]
+
+use binary_io::{AsyncBinPack, BinPack, BinaryIO, BinaryIoError, Endian};
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(endian = "little", size = 16)]
+struct Sample {
+    seq: u32,
+    values: [f32; 3],
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(version = 1)]
+struct Note {
+    #[binary_io(len = "varint")]
+    text: String,
+    tags: Vec<u16>,
+    extra: Option<u8>,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "u8", payload_size = 8, size = 9)]
+enum Message {
+    Sample(u32),
+    Note(#[binary_io(len = "u8")] String),
+}
+
+fn note(text: &str) -> Note {
+    Note {
+        text: text.to_string(),
+        tags: vec![1, 2, 3],
+        extra: Some(9),
+    }
+}
+
+#[tokio::test]
+async fn fixed_size_records_over_duplex() {
+    let (mut client, mut server) = tokio::io::duplex(16);
+    let writer = tokio::spawn(async move {
+        for seq in 0..10 {
+            let sample = Sample {
+                seq,
+                values: [seq as f32; 3],
+            };
+            sample.write_to(&mut client).await.unwrap();
+        }
+    });
+    for seq in 0..10 {
+        let sample = Sample::read_from(&mut server).await.unwrap();
+        assert_eq!(sample.seq, seq);
+    }
+    writer.await.unwrap();
+    // the writer is gone, the stream ends cleanly on a record boundary
+    assert!(Sample::read_from(&mut server).await.unwrap_err().is_eof());
+}
+
+#[tokio::test]
+async fn variable_size_records_leave_the_next_one_in_place() {
+    let (mut client, mut server) = tokio::io::duplex(4);
+    let writer = tokio::spawn(async move {
+        note("first").write_to(&mut client).await.unwrap();
+        note("").write_to_endian(&mut client, Endian::Big).await.unwrap();
+        Message::Note("hi".to_string()).write_to(&mut client).await.unwrap();
+        Message::Sample(7).write_to(&mut client).await.unwrap();
+    });
+    assert_eq!(Note::read_from(&mut server).await.unwrap(), note("first"));
+    assert_eq!(
+        Note::read_from_endian(&mut server, Endian::Big).await.unwrap(),
+        note("")
+    );
+    assert_eq!(
+        Message::read_from(&mut server).await.unwrap(),
+        Message::Note("hi".to_string())
+    );
+    assert_eq!(Message::read_from(&mut server).await.unwrap(), Message::Sample(7));
+    writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn large_records_are_read_in_one_go() {
+    let large = Note {
+        text: "x".repeat(10_000),
+        tags: (0..50_000).collect(),
+        extra: None,
+    };
+    let bytes = large.to_vec().unwrap();
+    let (mut client, mut server) = tokio::io::duplex(256);
+    let writer = tokio::spawn(async move { client.write_all(&bytes).await.unwrap() });
+    assert_eq!(Note::read_from(&mut server).await.unwrap(), large);
+    writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn same_bytes_as_the_blocking_api() {
+    let (mut client, mut server) = tokio::io::duplex(64);
+    note("same").write_to(&mut client).await.unwrap();
+    drop(client);
+    let mut received = vec![];
+    tokio::io::AsyncReadExt::read_to_end(&mut server, &mut received)
+        .await
+        .unwrap();
+    assert_eq!(received, note("same").to_vec().unwrap());
+}
+
+#[tokio::test]
+async fn truncated_and_invalid_streams() {
+    let bytes = note("cut short").to_vec().unwrap();
+    for len in 1..bytes.len() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&bytes[..len]).await.unwrap();
+        drop(client);
+        assert!(matches!(
+            Note::read_from(&mut server).await,
+            Err(BinaryIoError::Truncated)
+        ));
+    }
+    let (mut client, mut server) = tokio::io::duplex(64);
+    client.write_all(&[5; 9]).await.unwrap();
+    assert!(matches!(
+        Message::read_from(&mut server).await,
+        Err(BinaryIoError::InvalidDiscriminant { value: 5, .. })
+    ));
+}
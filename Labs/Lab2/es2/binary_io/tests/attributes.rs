@@ -0,0 +1,148 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError};
+
+const RESERVED: usize = 3;
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(magic = b"SENS", endian = "little")]
+struct Header {
+    #[binary_io(assert = (1..=2).contains(&version))]
+    version: u16,
+    #[binary_io(pad = RESERVED)]
+    count: u32,
+    #[binary_io(skip)]
+    cached_total: Option<f64>,
+    #[binary_io(magic = b"\xde\xad", assert = count <= 100 && checksum != 0)]
+    checksum: u8,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "u8", magic = b"EV")]
+enum Event {
+    Ping,
+    Data(#[binary_io(assert = __field0 > 0)] u8),
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct NotBinary(Vec<String>);
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct WithCache {
+    id: u8,
+    #[binary_io(skip)]
+    cache: NotBinary,
+}
+
+fn header() -> Header {
+    Header {
+        version: 2,
+        count: 10,
+        cached_total: Some(1.5),
+        checksum: 0x7f,
+    }
+}
+
+#[test]
+fn layout_on_disk() {
+    let bytes = header().to_vec().unwrap();
+    assert_eq!(
+        bytes,
+        [b'S', b'E', b'N', b'S', 2, 0, 0, 0, 0, 10, 0, 0, 0, 0xde, 0xad, 0x7f]
+    );
+}
+
+#[test]
+fn skipped_fields_use_default() {
+    let bytes = header().to_vec().unwrap();
+    let (decoded, rest) = Header::from_slice(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(decoded.cached_total, None);
+    assert_eq!(decoded.checksum, 0x7f);
+    let cached = WithCache {
+        id: 4,
+        cache: NotBinary(vec!["x".to_string()]),
+    };
+    let (decoded, _) = WithCache::from_slice(&cached.to_vec().unwrap()).unwrap();
+    assert_eq!(decoded.cache, NotBinary::default());
+}
+
+#[test]
+fn bad_container_magic() {
+    let mut bytes = header().to_vec().unwrap();
+    bytes[0] = b'X';
+    match Header::from_slice(&bytes) {
+        Err(BinaryIoError::BadMagic { expected, found }) => {
+            assert_eq!(expected, b"SENS");
+            assert_eq!(found, b"XENS");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn bad_field_magic() {
+    let mut bytes = header().to_vec().unwrap();
+    bytes[13] = 0;
+    let err = Header::from_slice(&bytes).unwrap_err();
+    assert!(matches!(
+        err,
+        BinaryIoError::BadMagic {
+            expected: b"\xde\xad",
+            ..
+        }
+    ));
+    assert!(err.to_string().contains("de, ad"), "{}", err);
+}
+
+#[test]
+fn failed_assertions() {
+    let mut bytes = header().to_vec().unwrap();
+    bytes[4] = 9;
+    match Header::from_slice(&bytes) {
+        Err(BinaryIoError::AssertionFailed { field, condition }) => {
+            assert_eq!(field, "Header.version");
+            assert!(condition.contains("contains"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    let mut bytes = header().to_vec().unwrap();
+    bytes[15] = 0;
+    let err = Header::from_slice(&bytes).unwrap_err();
+    assert!(matches!(
+        err,
+        BinaryIoError::AssertionFailed {
+            field: "Header.checksum",
+            ..
+        }
+    ));
+    assert!(matches!(
+        Event::from_slice(b"EV\x01\x00"),
+        Err(BinaryIoError::AssertionFailed { .. })
+    ));
+}
+
+#[test]
+fn padding_is_ignored_on_read() {
+    let mut bytes = header().to_vec().unwrap();
+    bytes[6..9].copy_from_slice(&[1, 2, 3]);
+    assert_eq!(Header::from_slice(&bytes).unwrap().0.count, 10);
+}
+
+#[test]
+fn magic_and_eof() {
+    assert!(Header::from_slice(&[]).unwrap_err().is_eof());
+    assert!(matches!(
+        Header::from_slice(b"SE"),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert!(matches!(
+        Header::from_slice(b"SENS"),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert!(Event::from_slice(&[]).unwrap_err().is_eof());
+    assert!(matches!(
+        Event::from_slice(b"EV"),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert_eq!(Event::from_slice(b"EV\x00").unwrap().0, Event::Ping);
+    assert_eq!(Event::from_slice(b"EV\x01\x05").unwrap().0, Event::Data(5));
+}
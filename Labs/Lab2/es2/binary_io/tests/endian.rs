@@ -0,0 +1,104 @@
+use binary_io::{BinPack, BinaryIO, Endian};
+use std::io::{BufWriter, Cursor};
+
+fn encode<T: BinPack>(value: &T, endian: Endian) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes_endian(&mut writer, endian).unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode<T: BinPack>(bytes: &[u8], endian: Endian) -> Option<T> {
+    T::from_bytes_endian(&mut Cursor::new(bytes), endian).ok()
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Record {
+    seq: u32,
+    values: [f32; 3],
+    timestamp: i64,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(endian = "big")]
+struct BigHeader {
+    magic: u16,
+    len: u32,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Nested {
+    header: BigHeader,
+    count: u16,
+}
+
+fn record() -> Record {
+    Record {
+        seq: 0x01020304,
+        values: [1.0, -2.5, 3.25],
+        timestamp: -7,
+    }
+}
+
+#[test]
+fn roundtrip_both_orders() {
+    for endian in [Endian::Little, Endian::Big] {
+        let bytes = encode(&record(), endian);
+        assert_eq!(bytes.len(), 4 + 12 + 8);
+        assert_eq!(decode::<Record>(&bytes, endian), Some(record()));
+    }
+}
+
+#[test]
+fn byte_order_on_disk() {
+    assert_eq!(encode(&0x01020304u32, Endian::Little), [4, 3, 2, 1]);
+    assert_eq!(encode(&0x01020304u32, Endian::Big), [1, 2, 3, 4]);
+    assert_eq!(&encode(&record(), Endian::Big)[..4], [1, 2, 3, 4]);
+    assert_eq!(&encode(&record(), Endian::Little)[..4], [4, 3, 2, 1]);
+    assert_eq!(encode(&1.0f32, Endian::Big), [0x3f, 0x80, 0, 0]);
+}
+
+#[test]
+fn wrong_order_does_not_roundtrip() {
+    let bytes = encode(&record(), Endian::Little);
+    assert_ne!(decode::<Record>(&bytes, Endian::Big), Some(record()));
+}
+
+#[test]
+fn le_be_shortcuts() {
+    let mut le = BufWriter::new(vec![]);
+    0xAABBu16.to_bytes_le(&mut le).unwrap();
+    let mut be = BufWriter::new(vec![]);
+    0xAABBu16.to_bytes_be(&mut be).unwrap();
+    assert_eq!(le.into_inner().unwrap(), [0xBB, 0xAA]);
+    assert_eq!(be.into_inner().unwrap(), [0xAA, 0xBB]);
+    assert_eq!(
+        u16::from_bytes_be(&mut Cursor::new([0xAA, 0xBB])).unwrap(),
+        0xAABB
+    );
+    assert_eq!(
+        u16::from_bytes_le(&mut Cursor::new([0xAA, 0xBB])).unwrap(),
+        0xBBAA
+    );
+}
+
+#[test]
+fn pinned_container_ignores_requested_order() {
+    let header = BigHeader {
+        magic: 0xCAFE,
+        len: 1,
+    };
+    assert_eq!(BigHeader::ENDIAN, Endian::Big);
+    for endian in [Endian::Little, Endian::Big] {
+        assert_eq!(encode(&header, endian), [0xCA, 0xFE, 0, 0, 0, 1]);
+    }
+    let nested = Nested {
+        header: BigHeader {
+            magic: 0xCAFE,
+            len: 1,
+        },
+        count: 2,
+    };
+    let bytes = encode(&nested, Endian::Little);
+    assert_eq!(bytes, [0xCA, 0xFE, 0, 0, 0, 1, 2, 0]);
+    assert_eq!(decode::<Nested>(&bytes, Endian::Little), Some(nested));
+}
@@ -0,0 +1,128 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError, Endian};
+use std::io::{BufWriter, Cursor};
+
+fn encode<T: BinPack>(value: &T) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes(&mut writer).unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode<T: BinPack>(bytes: &[u8]) -> Result<T, BinaryIoError> {
+    T::from_bytes(&mut Cursor::new(bytes))
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "u8")]
+enum Shape {
+    Point,
+    Circle(f32),
+    Rect { w: u16, h: u16 },
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "i32", endian = "little")]
+#[repr(i32)]
+enum Explicit {
+    One = 1,
+    Two,
+    Ten(u8) = 10,
+    Eleven(u8),
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "i32", payload_align = 8, payload_size = 16, endian = "little")]
+#[repr(i32)]
+enum Padded {
+    Small(u8) = 1,
+    Large([u32; 4]) = 2,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "u8", payload_size = 2)]
+enum TooBig {
+    Wide(u32),
+}
+
+#[test]
+fn implicit_discriminants() {
+    assert_eq!(encode(&Shape::Point), [0]);
+    let circle = encode(&Shape::Circle(1.0));
+    assert_eq!(circle[0], 1);
+    assert_eq!(circle.len(), 5);
+    let rect = Shape::Rect { w: 3, h: 4 };
+    let bytes = encode(&rect);
+    assert_eq!(bytes[0], 2);
+    assert_eq!(decode::<Shape>(&bytes).unwrap(), rect);
+    assert_eq!(decode::<Shape>(&circle).unwrap(), Shape::Circle(1.0));
+}
+
+#[test]
+fn explicit_discriminants() {
+    assert_eq!(encode(&Explicit::One), [1, 0, 0, 0]);
+    assert_eq!(encode(&Explicit::Two), [2, 0, 0, 0]);
+    assert_eq!(encode(&Explicit::Ten(5)), [10, 0, 0, 0, 5]);
+    assert_eq!(encode(&Explicit::Eleven(6)), [11, 0, 0, 0, 6]);
+    assert_eq!(
+        decode::<Explicit>(&[11, 0, 0, 0, 6]).unwrap(),
+        Explicit::Eleven(6)
+    );
+}
+
+#[test]
+fn padded_payload_has_fixed_size() {
+    let small = encode(&Padded::Small(0xAB));
+    let large = encode(&Padded::Large([1, 2, 3, 4]));
+    // 4 bytes of tag, 4 bytes to align the payload to 8, 16 bytes of payload
+    assert_eq!(small.len(), 24);
+    assert_eq!(large.len(), 24);
+    assert_eq!(&small[..9], [1, 0, 0, 0, 0, 0, 0, 0, 0xAB]);
+    assert!(small[9..].iter().all(|b| *b == 0));
+    let mut reader = Cursor::new([small, large].concat());
+    assert_eq!(
+        Padded::from_bytes(&mut reader).unwrap(),
+        Padded::Small(0xAB)
+    );
+    assert_eq!(
+        Padded::from_bytes(&mut reader).unwrap(),
+        Padded::Large([1, 2, 3, 4])
+    );
+    assert!(Padded::from_bytes(&mut reader).unwrap_err().is_eof());
+}
+
+#[test]
+fn unknown_discriminant_keeps_stream_aligned() {
+    let mut bytes = encode(&Padded::Small(1));
+    bytes[0] = 7;
+    bytes.extend(encode(&Padded::Small(2)));
+    let mut reader = Cursor::new(bytes);
+    match Padded::from_bytes(&mut reader) {
+        Err(BinaryIoError::InvalidDiscriminant { type_name, value }) => {
+            assert_eq!(type_name, "Padded");
+            assert_eq!(value, 7);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(Padded::from_bytes(&mut reader).unwrap(), Padded::Small(2));
+}
+
+#[test]
+fn truncated_payload() {
+    let bytes = encode(&Padded::Large([1, 2, 3, 4]));
+    assert!(matches!(
+        decode::<Padded>(&bytes[..10]),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert!(matches!(
+        decode::<Shape>(&[1, 0]),
+        Err(BinaryIoError::Truncated)
+    ));
+}
+
+#[test]
+fn oversized_payload_is_refused() {
+    let mut writer = BufWriter::new(vec![]);
+    let err = TooBig::Wide(1)
+        .to_bytes_endian(&mut writer, Endian::Little)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
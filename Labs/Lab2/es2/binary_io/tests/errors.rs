@@ -0,0 +1,78 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError};
+use std::io::{BufWriter, Cursor, Read};
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Pair {
+    a: u32,
+    b: u32,
+}
+
+fn encode<T: BinPack>(value: &T) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes(&mut writer).unwrap();
+    writer.into_inner().unwrap()
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "denied",
+        ))
+    }
+}
+
+#[test]
+fn empty_input_is_eof() {
+    let err = Pair::from_bytes(&mut Cursor::new(&[] as &[u8])).unwrap_err();
+    assert!(err.is_eof());
+}
+
+#[test]
+fn short_record_is_truncated() {
+    let bytes = encode(&Pair { a: 1, b: 2 });
+    for len in 1..bytes.len() {
+        let err = Pair::from_bytes(&mut Cursor::new(&bytes[..len])).unwrap_err();
+        assert!(
+            matches!(err, BinaryIoError::Truncated),
+            "{} bytes gave {:?}",
+            len,
+            err
+        );
+    }
+}
+
+#[test]
+fn eof_after_last_record() {
+    let mut bytes = encode(&Pair { a: 1, b: 2 });
+    bytes.extend(encode(&Pair { a: 3, b: 4 }));
+    let mut reader = Cursor::new(bytes);
+    assert_eq!(Pair::from_bytes(&mut reader).unwrap(), Pair { a: 1, b: 2 });
+    assert_eq!(Pair::from_bytes(&mut reader).unwrap(), Pair { a: 3, b: 4 });
+    assert!(Pair::from_bytes(&mut reader).unwrap_err().is_eof());
+}
+
+#[test]
+fn io_errors_are_forwarded() {
+    let mut reader = std::io::BufReader::new(FailingReader);
+    let mut buf = [0u8; 4];
+    match binary_io::read_exact(&mut reader, &mut buf) {
+        Err(BinaryIoError::IoError(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied)
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_bool() {
+    match bool::from_bytes(&mut Cursor::new([7u8])) {
+        Err(BinaryIoError::InvalidDiscriminant { type_name, value }) => {
+            assert_eq!(type_name, "bool");
+            assert_eq!(value, 7);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,114 @@
+use binary_io::{BinaryIO, BinaryIoError, FramedReader, FramedWriter};
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(endian = "little")]
+struct Sample {
+    seq: u32,
+    values: [f32; 4],
+}
+
+// frame header + 20 byte payload
+const FRAME: usize = 28;
+
+fn sample(seq: u32) -> Sample {
+    Sample {
+        seq,
+        values: [seq as f32; 4],
+    }
+}
+
+fn frames(count: u32) -> Vec<u8> {
+    let mut writer = FramedWriter::new(vec![]);
+    for seq in 0..count {
+        writer.write(&sample(seq)).unwrap();
+    }
+    writer.into_inner()
+}
+
+#[test]
+fn frame_layout() {
+    let bytes = frames(1);
+    assert_eq!(bytes.len(), FRAME);
+    assert_eq!(bytes[..4], [20, 0, 0, 0]);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&bytes[..4]);
+    hasher.update(&bytes[8..]);
+    assert_eq!(bytes[4..8], hasher.finalize().to_le_bytes());
+}
+
+#[test]
+fn roundtrip() {
+    let bytes = frames(3);
+    let mut reader = FramedReader::new(&bytes[..]);
+    for seq in 0..3 {
+        assert_eq!(reader.read::<Sample>().unwrap(), sample(seq));
+    }
+    assert_eq!(reader.offset(), 3 * FRAME as u64);
+    assert!(reader.read::<Sample>().unwrap_err().is_eof());
+}
+
+#[test]
+fn corrupted_payload_is_reported_with_its_offset() {
+    let mut bytes = frames(3);
+    bytes[FRAME + 10] ^= 0xff;
+    let mut reader = FramedReader::new(&bytes[..]);
+    assert_eq!(reader.read::<Sample>().unwrap(), sample(0));
+    match reader.read::<Sample>() {
+        Err(BinaryIoError::ChecksumMismatch { offset, .. }) => assert_eq!(offset, FRAME as u64),
+        other => panic!("unexpected {:?}", other),
+    }
+    // the bad frame is only left behind by resync
+    assert_eq!(reader.resync().unwrap(), FRAME as u64);
+    assert_eq!(reader.read::<Sample>().unwrap(), sample(2));
+}
+
+#[test]
+fn resync_skips_garbage_between_frames() {
+    let bytes = frames(2);
+    let mut garbled = bytes[..FRAME].to_vec();
+    garbled.extend_from_slice(&[0x13; 7]);
+    garbled.extend_from_slice(&bytes[FRAME..]);
+    let mut reader = FramedReader::new(&garbled[..]);
+    reader.read::<Sample>().unwrap();
+    assert!(reader.read::<Sample>().is_err());
+    assert_eq!(reader.resync().unwrap(), 7);
+    assert_eq!(reader.read::<Sample>().unwrap(), sample(1));
+    // nothing to skip when the reader is already on a good frame
+    assert_eq!(reader.resync().unwrap(), 0);
+}
+
+#[test]
+fn torn_record_at_the_end() {
+    let bytes = frames(2);
+    let torn = &bytes[..bytes.len() - 5];
+    let mut reader = FramedReader::new(torn);
+    reader.read::<Sample>().unwrap();
+    assert!(matches!(
+        reader.read::<Sample>(),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert_eq!(reader.resync().unwrap(), (FRAME - 5) as u64);
+    assert!(reader.read::<Sample>().unwrap_err().is_eof());
+}
+
+#[test]
+fn corrupted_length() {
+    let mut bytes = frames(2);
+    bytes[3] = 0x7f;
+    let mut reader = FramedReader::new(&bytes[..]).with_max_frame_len(1024);
+    assert!(matches!(
+        reader.read::<Sample>(),
+        Err(BinaryIoError::FrameTooLong { offset: 0, .. })
+    ));
+    assert_eq!(reader.resync().unwrap(), FRAME as u64);
+    assert_eq!(reader.read::<Sample>().unwrap(), sample(1));
+
+    // a length that still fits is caught by the checksum
+    let mut bytes = frames(2);
+    bytes[0] = 4;
+    let mut reader = FramedReader::new(&bytes[..]);
+    assert!(matches!(
+        reader.read::<Sample>(),
+        Err(BinaryIoError::ChecksumMismatch { offset: 0, .. })
+    ));
+}
@@ -0,0 +1,85 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError, Endian, HashWriter};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::io::{Read, Write};
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Sample {
+    seq: u32,
+    values: [f32; 2],
+    #[binary_io(len = "u8")]
+    label: String,
+}
+
+fn sample(seq: u32) -> Sample {
+    Sample {
+        seq,
+        values: [0.5, 1.5],
+        label: format!("s{}", seq),
+    }
+}
+
+#[test]
+fn to_vec_and_from_slice() {
+    let bytes = sample(1).to_vec().unwrap();
+    let (decoded, rest) = Sample::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, sample(1));
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn from_slice_returns_the_remainder() {
+    let mut bytes = sample(1).to_vec_endian(Endian::Big).unwrap();
+    bytes.extend(sample(2).to_vec_endian(Endian::Big).unwrap());
+    bytes.extend([0xAA, 0xBB]);
+    let (first, rest) = Sample::from_slice_endian(&bytes, Endian::Big).unwrap();
+    let (second, rest) = Sample::from_slice_endian(rest, Endian::Big).unwrap();
+    assert_eq!((first, second), (sample(1), sample(2)));
+    assert_eq!(rest, [0xAA, 0xBB]);
+    assert!(matches!(
+        Sample::from_slice_endian(rest, Endian::Big),
+        Err(BinaryIoError::Truncated)
+    ));
+}
+
+#[test]
+fn plain_read_and_write() {
+    let mut buf: Vec<u8> = vec![];
+    sample(3).to_bytes(&mut buf).unwrap();
+    let mut reader: &[u8] = &buf;
+    assert_eq!(Sample::from_bytes(&mut reader).unwrap(), sample(3));
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn trait_objects() {
+    let mut buf: Vec<u8> = vec![];
+    {
+        let writer: &mut dyn Write = &mut buf;
+        sample(4).to_bytes(writer).unwrap();
+    }
+    let mut slice: &[u8] = &buf;
+    let reader: &mut dyn Read = &mut slice;
+    assert_eq!(Sample::from_bytes(reader).unwrap(), sample(4));
+}
+
+#[test]
+fn hash_of_encoded_record() {
+    let hash = |s: &Sample| {
+        let mut hasher = HashWriter(DefaultHasher::new());
+        s.to_bytes(&mut hasher).unwrap();
+        hasher.0.finish()
+    };
+    assert_eq!(hash(&sample(5)), hash(&sample(5)));
+    assert_ne!(hash(&sample(5)), hash(&sample(6)));
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_pair() {
+    let (mut a, mut b) = std::os::unix::net::UnixStream::pair().unwrap();
+    sample(7).to_bytes(&mut a).unwrap();
+    drop(a);
+    assert_eq!(Sample::from_bytes(&mut b).unwrap(), sample(7));
+    assert!(Sample::from_bytes(&mut b).unwrap_err().is_eof());
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "mmap")]
+
+use binary_io::{BinPack, BinaryIO, BinaryIoError, Endian, FixedSize, MappedRecords};
+use std::io::{Read, Seek, Write};
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(endian = "little", magic = b"R", size = 13)]
+struct Record {
+    seq: u32,
+    values: [f32; 2],
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(size = 6)]
+struct Tagged {
+    #[binary_io(assert = format == 1)]
+    format: u16,
+    value: u32,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Variable {
+    name: String,
+}
+
+// whether a type is FixedSize, without failing to compile when it is not: the inherent const is
+// preferred when its bound holds, the trait one is the fallback
+struct Probe<T>(std::marker::PhantomData<T>);
+
+trait NotFixed {
+    const FIXED: bool = false;
+}
+
+impl<T> NotFixed for Probe<T> {}
+
+impl<T: FixedSize> Probe<T> {
+    const FIXED: bool = true;
+}
+
+const HEADER: &[u8] = b"HEADER";
+
+fn record(seq: u32) -> Record {
+    Record {
+        seq,
+        values: [seq as f32, -1.0],
+    }
+}
+
+fn file_with(count: u32) -> std::fs::File {
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(HEADER).unwrap();
+    for seq in 0..count {
+        record(seq).to_bytes(&mut file).unwrap();
+    }
+    file
+}
+
+#[test]
+fn fixed_sizes() {
+    assert_eq!(Record::ENCODED_SIZE, 13);
+    assert_eq!(<[u16; 3]>::ENCODED_SIZE, 6);
+    assert_eq!(bool::ENCODED_SIZE, 1);
+    // types without a `size` are not FixedSize, so MappedRecords cannot hold them
+    assert_eq!(Variable::STATIC_SIZE, None);
+    const { assert!(!Probe::<Variable>::FIXED) };
+    const { assert!(Probe::<Record>::FIXED) };
+}
+
+#[test]
+fn get_and_iterate() {
+    let file = file_with(5);
+    let records = MappedRecords::<Record>::open_read_only(&file, HEADER.len() as u64).unwrap();
+    assert_eq!(records.len(), 5);
+    assert_eq!(records.get(3).unwrap(), record(3));
+    let all: Vec<_> = records.iter().map(Result::unwrap).collect();
+    assert_eq!(all, (0..5).map(record).collect::<Vec<_>>());
+    assert!(matches!(
+        records.get(5),
+        Err(BinaryIoError::OutOfBounds { index: 5, len: 5 })
+    ));
+}
+
+#[test]
+fn set_in_place() {
+    let mut file = file_with(3);
+    let mut records = MappedRecords::<Record>::open(&file, HEADER.len() as u64).unwrap();
+    records.set(1, &record(42)).unwrap();
+    assert!(matches!(
+        records.set(3, &record(0)),
+        Err(BinaryIoError::OutOfBounds { .. })
+    ));
+    records.flush().unwrap();
+    assert_eq!(records.get(1).unwrap(), record(42));
+
+    let mut bytes = vec![];
+    file.rewind().unwrap();
+    file.read_to_end(&mut bytes).unwrap();
+    let (second, _) = Record::from_slice(&bytes[HEADER.len() + Record::ENCODED_SIZE..]).unwrap();
+    assert_eq!(second, record(42));
+    assert_eq!(&bytes[..HEADER.len()], HEADER);
+}
+
+#[test]
+fn read_only_mapping_refuses_writes() {
+    let file = file_with(1);
+    let mut records = MappedRecords::<Record>::open_read_only(&file, HEADER.len() as u64).unwrap();
+    assert!(matches!(
+        records.set(0, &record(1)),
+        Err(BinaryIoError::IoError(e)) if e.kind() == std::io::ErrorKind::PermissionDenied
+    ));
+}
+
+#[test]
+fn layout_is_validated_at_open() {
+    let mut file = file_with(2);
+    // a torn record at the end
+    file.write_all(&[1, 2, 3]).unwrap();
+    assert!(matches!(
+        MappedRecords::<Record>::open(&file, HEADER.len() as u64),
+        Err(BinaryIoError::Misaligned { len: 29, record_size: 13 })
+    ));
+    // wrong offset, the records do not start where they are expected
+    let file = file_with(2);
+    assert!(MappedRecords::<Record>::open(&file, 0).is_err());
+    assert!(matches!(
+        MappedRecords::<Record>::open(&file, 1000),
+        Err(BinaryIoError::Truncated)
+    ));
+}
+
+#[test]
+fn empty_file() {
+    let file = file_with(0);
+    let records = MappedRecords::<Record>::open(&file, HEADER.len() as u64).unwrap();
+    assert!(records.is_empty());
+    assert_eq!(records.iter().count(), 0);
+    let empty = tempfile::tempfile().unwrap();
+    assert!(MappedRecords::<Record>::open(&empty, 0).unwrap().is_empty());
+}
+
+fn swapped() -> Endian {
+    match Endian::NATIVE {
+        Endian::Little => Endian::Big,
+        Endian::Big => Endian::Little,
+    }
+}
+
+// only a schema that can tell catches a file written in the other byte order
+#[test]
+fn wrong_byte_order_is_caught_by_an_assert_field() {
+    let swapped = swapped();
+    let mut file = tempfile::tempfile().unwrap();
+    for value in 0..3 {
+        Tagged { format: 1, value }
+            .to_bytes_endian(&mut file, swapped)
+            .unwrap();
+    }
+    assert!(matches!(
+        MappedRecords::<Tagged>::open(&file, 0),
+        Err(BinaryIoError::AssertionFailed { .. })
+    ));
+}
+
+#[test]
+fn wrong_byte_order_of_plain_numbers_is_not_caught() {
+    let mut file = tempfile::tempfile().unwrap();
+    [1.5f32, -2.0]
+        .to_bytes_endian(&mut file, swapped())
+        .unwrap();
+    let records = MappedRecords::<[f32; 2]>::open(&file, 0).unwrap();
+    assert_ne!(records.get(0).unwrap(), [1.5, -2.0]);
+}
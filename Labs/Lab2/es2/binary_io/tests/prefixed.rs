@@ -0,0 +1,135 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError, Endian, LengthPrefix, LengthPrefixed};
+use std::io::{BufWriter, Cursor};
+
+fn encode<T: BinPack>(value: &T) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes_le(&mut writer).unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode<T: BinPack>(bytes: &[u8]) -> Result<T, BinaryIoError> {
+    T::from_bytes_le(&mut Cursor::new(bytes))
+}
+
+fn encode_prefixed<T: LengthPrefixed>(value: &T, prefix: LengthPrefix) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes_prefixed(&mut writer, Endian::Little, prefix).unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode_prefixed<T: LengthPrefixed>(bytes: &[u8], prefix: LengthPrefix) -> Result<T, BinaryIoError> {
+    T::from_bytes_prefixed(&mut Cursor::new(bytes), Endian::Little, prefix)
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Message {
+    #[binary_io(len = "u8")]
+    name: String,
+    #[binary_io(len = "varint")]
+    readings: Vec<f32>,
+    note: Option<String>,
+    #[binary_io(len = "u16")]
+    tags: Option<Box<[u16]>>,
+    id: u32,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(tag = "u8")]
+enum Event {
+    Text(#[binary_io(len = "u8")] String),
+    Samples {
+        #[binary_io(len = "varint")]
+        values: Vec<u8>,
+    },
+}
+
+fn message() -> Message {
+    Message {
+        name: "sensor ☃".to_string(),
+        readings: vec![1.0, 2.0, 3.0],
+        note: None,
+        tags: Some(vec![1, 2].into_boxed_slice()),
+        id: 9,
+    }
+}
+
+#[test]
+fn default_prefix_is_u32() {
+    assert_eq!(encode(&vec![7u8, 8]), [2, 0, 0, 0, 7, 8]);
+    assert_eq!(encode(&"hi".to_string()), [2, 0, 0, 0, b'h', b'i']);
+    assert_eq!(encode(&Some(5u8)), [1, 5]);
+    assert_eq!(encode(&None::<u8>), [0]);
+    assert_eq!(
+        decode::<Box<[u16]>>(&[1, 0, 0, 0, 3, 0]).unwrap(),
+        vec![3].into_boxed_slice()
+    );
+}
+
+#[test]
+fn prefix_widths() {
+    let values = vec![1u8, 2, 3];
+    assert_eq!(encode_prefixed(&values, LengthPrefix::U8), [3, 1, 2, 3]);
+    assert_eq!(encode_prefixed(&values, LengthPrefix::U16), [3, 0, 1, 2, 3]);
+    assert_eq!(encode_prefixed(&values, LengthPrefix::Varint), [3, 1, 2, 3]);
+    let long = vec![0u8; 300];
+    let bytes = encode_prefixed(&long, LengthPrefix::Varint);
+    assert_eq!(&bytes[..2], [0xAC, 0x02]);
+    assert_eq!(decode_prefixed::<Vec<u8>>(&bytes, LengthPrefix::Varint).unwrap(), long);
+}
+
+#[test]
+fn length_that_does_not_fit_the_prefix() {
+    let mut writer = BufWriter::new(vec![]);
+    let err = vec![0u8; 256]
+        .to_bytes_prefixed(&mut writer, Endian::Little, LengthPrefix::U8)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn derived_struct_roundtrip() {
+    let bytes = encode(&message());
+    assert_eq!(bytes[0] as usize, "sensor ☃".len());
+    assert_eq!(decode::<Message>(&bytes).unwrap(), message());
+    let mut with_note = message();
+    with_note.note = Some("checked".to_string());
+    with_note.tags = None;
+    assert_eq!(decode::<Message>(&encode(&with_note)).unwrap(), with_note);
+}
+
+#[test]
+fn derived_enum_roundtrip() {
+    let text = Event::Text("hello".to_string());
+    assert_eq!(encode(&text), [0, 5, b'h', b'e', b'l', b'l', b'o']);
+    assert_eq!(decode::<Event>(&encode(&text)).unwrap(), text);
+    let samples = Event::Samples { values: vec![4, 5] };
+    assert_eq!(decode::<Event>(&encode(&samples)).unwrap(), samples);
+}
+
+#[test]
+fn truncated_and_invalid_data() {
+    let bytes = encode(&message());
+    for len in 1..bytes.len() {
+        assert!(matches!(
+            decode::<Message>(&bytes[..len]),
+            Err(BinaryIoError::Truncated)
+        ));
+    }
+    assert!(matches!(
+        decode::<String>(&[2, 0, 0, 0, 0xff, 0xfe]),
+        Err(BinaryIoError::InvalidUtf8(_))
+    ));
+    assert!(matches!(
+        decode_prefixed::<Vec<u8>>(&[0xff; 11], LengthPrefix::Varint),
+        Err(BinaryIoError::LengthOverflow)
+    ));
+}
+
+#[test]
+fn huge_length_does_not_preallocate() {
+    // claims 4 billion elements but has none
+    assert!(matches!(
+        decode::<Vec<u64>>(&[0xff, 0xff, 0xff, 0xff]),
+        Err(BinaryIoError::Truncated)
+    ));
+}
@@ -0,0 +1,90 @@
+use binary_io::{BinPack, BinaryIO};
+use std::io::{BufWriter, Cursor};
+
+fn encode<T: BinPack>(value: &T) -> Vec<u8> {
+    let mut writer = BufWriter::new(vec![]);
+    value.to_bytes(&mut writer).unwrap();
+    writer.into_inner().unwrap()
+}
+
+fn decode<T: BinPack>(bytes: &[u8]) -> Option<T> {
+    T::from_bytes(&mut Cursor::new(bytes)).ok()
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Unpadded {
+    flag: bool,
+    id: u32,
+    small: u8,
+    value: f64,
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Tuple(i16, [u8; 3]);
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Unit;
+
+#[derive(Debug, PartialEq, BinaryIO)]
+struct Generic<T> {
+    items: [T; 2],
+}
+
+#[test]
+fn primitives_roundtrip() {
+    assert_eq!(decode::<u8>(&encode(&0xABu8)), Some(0xAB));
+    assert_eq!(decode::<i32>(&encode(&-42i32)), Some(-42));
+    assert_eq!(decode::<u64>(&encode(&u64::MAX)), Some(u64::MAX));
+    assert_eq!(decode::<i128>(&encode(&i128::MIN)), Some(i128::MIN));
+    assert_eq!(decode::<f32>(&encode(&1.5f32)), Some(1.5));
+    assert_eq!(decode::<f64>(&encode(&-0.25f64)), Some(-0.25));
+    assert_eq!(decode::<bool>(&encode(&true)), Some(true));
+    assert_eq!(
+        decode::<[u16; 4]>(&encode(&[1u16, 2, 3, 4])),
+        Some([1, 2, 3, 4])
+    );
+}
+
+#[test]
+fn struct_has_no_padding() {
+    let value = Unpadded {
+        flag: true,
+        id: 7,
+        small: 3,
+        value: 2.5,
+    };
+    let bytes = encode(&value);
+    // 1 + 4 + 1 + 8, no alignment padding between the fields
+    assert_eq!(bytes.len(), 14);
+    assert_eq!(decode::<Unpadded>(&bytes), Some(value));
+}
+
+#[test]
+fn tuple_unit_and_generic_structs() {
+    let tuple = Tuple(-5, [1, 2, 3]);
+    assert_eq!(encode(&tuple).len(), 5);
+    assert_eq!(decode::<Tuple>(&encode(&tuple)), Some(tuple));
+    assert!(encode(&Unit).is_empty());
+    assert_eq!(decode::<Unit>(&[]), Some(Unit));
+    let generic = Generic {
+        items: [1.0f32, 2.0],
+    };
+    assert_eq!(decode::<Generic<f32>>(&encode(&generic)), Some(generic));
+}
+
+#[test]
+fn invalid_bool_is_rejected() {
+    assert_eq!(decode::<bool>(&[2]), None);
+}
+
+#[test]
+fn short_input_is_rejected() {
+    let bytes = encode(&Unpadded {
+        flag: false,
+        id: 1,
+        small: 2,
+        value: 3.0,
+    });
+    assert_eq!(decode::<Unpadded>(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(decode::<Unpadded>(&[]), None);
+}
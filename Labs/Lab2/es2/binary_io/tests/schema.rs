@@ -0,0 +1,241 @@
+use binary_io::inspect::{inspect, Layout, Registry, Report};
+use binary_io::{BinPack, BinaryIO, FieldInfo, FixedSize};
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(magic = b"HD", endian = "little")]
+struct Header {
+    version: u16,
+    #[binary_io(pad = 2)]
+    count: u32,
+}
+
+// `size` is checked at compile time, a mismatch fails the build
+#[derive(Debug, BinaryIO)]
+#[binary_io(magic = b"HD", endian = "little", size = 10)]
+struct CheckedHeader {
+    version: u16,
+    #[binary_io(pad = 2)]
+    count: u32,
+}
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(size = 8)]
+struct CheckedPair<T>(T, T);
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(endian = "little")]
+struct Reading {
+    seq: u32,
+    values: [f32; 2],
+    #[binary_io(skip)]
+    cached: Option<f32>,
+}
+
+#[derive(Debug, BinaryIO)]
+struct Named {
+    id: u8,
+    #[binary_io(len = "u8")]
+    name: String,
+    flag: bool,
+}
+
+#[derive(Debug, BinaryIO)]
+struct Pair<T>(T, T);
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(tag = "u8", payload_align = 4, payload_size = 8)]
+enum Padded {
+    A(u32),
+    B(u64),
+}
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(tag = "u16")]
+enum Uniform {
+    A(i32),
+    B(f32),
+}
+
+#[derive(Debug, BinaryIO)]
+#[binary_io(tag = "u16")]
+enum Mixed {
+    A(i32),
+    B(u8),
+}
+
+fn field(name: &'static str, offset: usize, size: usize, type_name: &'static str) -> FieldInfo {
+    FieldInfo {
+        name,
+        offset: Some(offset),
+        size: Some(size),
+        type_name,
+    }
+}
+
+#[test]
+fn static_sizes() {
+    assert_eq!(u16::STATIC_SIZE, Some(2));
+    assert_eq!(bool::STATIC_SIZE, Some(1));
+    assert_eq!(<[u64; 3]>::STATIC_SIZE, Some(24));
+    assert_eq!(<Vec<u8>>::STATIC_SIZE, None);
+    assert_eq!(<[String; 2]>::STATIC_SIZE, None);
+    assert_eq!(Header::STATIC_SIZE, Some(10));
+    assert_eq!(Reading::STATIC_SIZE, Some(12));
+    assert_eq!(Named::STATIC_SIZE, None);
+    assert_eq!(Pair::<u16>::STATIC_SIZE, Some(4));
+    assert_eq!(Padded::STATIC_SIZE, Some(12));
+    assert_eq!(Uniform::STATIC_SIZE, Some(6));
+    assert_eq!(Mixed::STATIC_SIZE, None);
+}
+
+#[test]
+fn checked_and_encoded_sizes() {
+    assert_eq!(CheckedHeader::STATIC_SIZE, Some(10));
+    assert_eq!(Some(CheckedHeader::ENCODED_SIZE), Header::STATIC_SIZE);
+    assert_eq!(CheckedPair::<u32>::ENCODED_SIZE, 8);
+    let header = CheckedHeader {
+        version: 1,
+        count: 2,
+    };
+    assert_eq!(header.to_vec().unwrap().len(), CheckedHeader::ENCODED_SIZE);
+}
+
+#[test]
+fn static_size_matches_encoding() {
+    let header = Header {
+        version: 1,
+        count: 3,
+    };
+    assert_eq!(Some(header.to_vec().unwrap().len()), Header::STATIC_SIZE);
+    let reading = Reading {
+        seq: 1,
+        values: [0.0; 2],
+        cached: Some(2.0),
+    };
+    let bytes = reading.to_vec().unwrap();
+    assert_eq!(Some(bytes.len()), Reading::STATIC_SIZE);
+    assert_eq!(Reading::from_slice(&bytes).unwrap().0.cached, None);
+    assert_eq!(Some(Padded::A(1).to_vec().unwrap().len()), Padded::STATIC_SIZE);
+    assert_eq!(Some(Uniform::B(1.0).to_vec().unwrap().len()), Uniform::STATIC_SIZE);
+}
+
+#[test]
+fn struct_schema() {
+    assert_eq!(
+        Header::SCHEMA,
+        [
+            field("(magic)", 0, 2, "[u8; 2]"),
+            field("version", 2, 2, "u16"),
+            field("(padding)", 4, 2, "[u8; 2]"),
+            field("count", 6, 4, "u32"),
+        ]
+    );
+    assert_eq!(
+        Reading::SCHEMA,
+        [field("seq", 0, 4, "u32"), field("values", 4, 8, "[f32; 2]")]
+    );
+    assert_eq!(Pair::<u8>::SCHEMA[1], field("1", 1, 1, "T"));
+}
+
+#[test]
+fn variable_size_fields() {
+    assert_eq!(Named::SCHEMA[0], field("id", 0, 1, "u8"));
+    assert_eq!(
+        Named::SCHEMA[1],
+        FieldInfo {
+            name: "name",
+            offset: Some(1),
+            size: None,
+            type_name: "String",
+        }
+    );
+    assert_eq!(Named::SCHEMA[2].offset, None);
+    assert_eq!(Named::SCHEMA[2].size, Some(1));
+}
+
+#[test]
+fn enum_schema() {
+    assert_eq!(
+        Padded::SCHEMA,
+        [
+            field("(tag)", 0, 1, "u8"),
+            field("(padding)", 1, 3, "padding"),
+            field("(payload)", 4, 8, "A | B"),
+        ]
+    );
+    assert_eq!(Mixed::SCHEMA[1].size, None);
+}
+
+fn readings(count: u32) -> Vec<u8> {
+    let mut bytes = Header {
+        version: 1,
+        count,
+    }
+    .to_vec()
+    .unwrap();
+    for seq in 0..count {
+        let reading = Reading {
+            seq,
+            values: [seq as f32, 0.5],
+            cached: None,
+        };
+        reading.to_bytes(&mut bytes).unwrap();
+    }
+    bytes
+}
+
+fn run(bytes: &[u8], count: Option<usize>) -> (Report, String) {
+    let mut registry = Registry::new();
+    registry
+        .register::<Header>("header")
+        .register::<Reading>("reading");
+    let layout = Layout {
+        header: registry.get("header"),
+        record: registry.get("reading").unwrap(),
+        count,
+        endian: None,
+    };
+    let mut out = vec![];
+    let report = inspect(&mut out, bytes, &layout).unwrap();
+    (report, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn inspect_records() {
+    let (report, out) = run(&readings(2), None);
+    assert_eq!(
+        report,
+        Report {
+            records: 2,
+            ..Report::default()
+        }
+    );
+    assert!(out.contains("0x00000000  header header (10 bytes)"), "{}", out);
+    assert!(out.contains("0x00000016  #1 reading (12 bytes)"), "{}", out);
+    assert!(out.contains("+0x0004  values  [f32; 2]  00 00 80 3f 00 00 00 3f"), "{}", out);
+    assert!(out.contains("= Reading { seq: 1, values: [1.0, 0.5], cached: None }"), "{}", out);
+}
+
+#[test]
+fn inspect_flags_truncated_and_trailing_bytes() {
+    let bytes = readings(3);
+    let (report, out) = run(&bytes[..bytes.len() - 5], None);
+    assert_eq!(report.records, 2);
+    assert_eq!(report.truncated, Some((34, 7)));
+    assert!(out.contains("0x00000022  #2 reading: truncated, only 7 bytes left"), "{}", out);
+
+    let (report, out) = run(&bytes, Some(2));
+    assert_eq!(report.records, 2);
+    assert_eq!(report.trailing, Some((34, 12)));
+    assert!(out.contains("0x00000022  12 trailing bytes"), "{}", out);
+}
+
+#[test]
+fn inspect_skips_invalid_fixed_size_records() {
+    let mut bytes = readings(1);
+    bytes[0] = b'X';
+    let (report, out) = run(&bytes, None);
+    assert_eq!(report.errors, 1);
+    assert_eq!(report.records, 1);
+    assert!(out.contains("Bad magic number"), "{}", out);
+}
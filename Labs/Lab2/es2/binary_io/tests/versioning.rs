@@ -0,0 +1,191 @@
+use binary_io::{BinPack, BinaryIO, BinaryIoError, Endian};
+
+// the same record as it evolves: v2 adds `checksum`, v3 widens `temperature` to f64
+mod v1 {
+    use binary_io::BinaryIO;
+
+    #[derive(Debug, PartialEq, BinaryIO)]
+    #[binary_io(endian = "little", version = 1)]
+    pub struct Reading {
+        pub seq: u32,
+        pub temperature: f32,
+    }
+}
+
+mod v2 {
+    use binary_io::BinaryIO;
+
+    #[derive(Debug, PartialEq, BinaryIO)]
+    #[binary_io(endian = "little", version = 2)]
+    pub struct Reading {
+        pub seq: u32,
+        pub temperature: f32,
+        #[binary_io(since = 2)]
+        pub checksum: u16,
+    }
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(endian = "little", version = 3, migrate = migrate_reading)]
+struct Reading {
+    seq: u32,
+    temperature: f64,
+    #[binary_io(since = 2)]
+    checksum: u16,
+}
+
+// layout of the body before v3, the version header is already stripped when the hook runs
+#[derive(BinaryIO)]
+struct ReadingBodyV1 {
+    seq: u32,
+    temperature: f32,
+}
+
+fn migrate_reading(
+    version: u16,
+    payload: &[u8],
+    endian: Endian,
+) -> Result<Option<Reading>, BinaryIoError> {
+    let (old, rest) = ReadingBodyV1::from_slice_endian(payload, endian)?;
+    let checksum = match version {
+        1 => 0xffff,
+        _ => u16::from_slice_endian(rest, endian)?.0,
+    };
+    Ok(Some(Reading {
+        seq: old.seq,
+        temperature: old.temperature.into(),
+        checksum,
+    }))
+}
+
+#[derive(Debug, PartialEq, BinaryIO)]
+#[binary_io(magic = b"EV", tag = "u8", version = 1)]
+enum Event {
+    Start,
+    Stop(#[binary_io(since = 1)] u32),
+}
+
+#[test]
+fn version_header() {
+    let bytes = v1::Reading {
+        seq: 7,
+        temperature: 1.0,
+    }
+    .to_vec()
+    .unwrap();
+    assert_eq!(bytes, [1, 0, 8, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0x80, 0x3f]);
+    assert_eq!(v1::Reading::STATIC_SIZE, Some(14));
+    assert_eq!(v1::Reading::SCHEMA[0].name, "(version)");
+    assert_eq!(v1::Reading::SCHEMA[2].offset, Some(6));
+    assert_eq!(
+        Event::Stop(3).to_vec_endian(Endian::Big).unwrap(),
+        [b'E', b'V', 0, 1, 0, 0, 0, 5, 1, 0, 0, 0, 3]
+    );
+}
+
+#[test]
+fn older_records_get_defaults() {
+    let bytes = v1::Reading {
+        seq: 7,
+        temperature: 1.5,
+    }
+    .to_vec()
+    .unwrap();
+    let (decoded, rest) = v2::Reading::from_slice(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        decoded,
+        v2::Reading {
+            seq: 7,
+            temperature: 1.5,
+            checksum: 0,
+        }
+    );
+}
+
+#[test]
+fn newer_records_are_read_without_their_new_fields() {
+    let mut bytes = v2::Reading {
+        seq: 1,
+        temperature: 2.0,
+        checksum: 0xabcd,
+    }
+    .to_vec()
+    .unwrap();
+    v2::Reading {
+        seq: 2,
+        temperature: 3.0,
+        checksum: 0,
+    }
+    .to_bytes(&mut bytes)
+    .unwrap();
+    // the unknown checksum is skipped, so the next record is still found
+    let (first, rest) = v1::Reading::from_slice(&bytes).unwrap();
+    let (second, rest) = v1::Reading::from_slice(rest).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!((first.seq, second.seq), (1, 2));
+    assert_eq!(second.temperature, 3.0);
+}
+
+#[test]
+fn migration_hook() {
+    let old = v1::Reading {
+        seq: 4,
+        temperature: 0.25,
+    }
+    .to_vec()
+    .unwrap();
+    assert_eq!(
+        Reading::from_slice(&old).unwrap().0,
+        Reading {
+            seq: 4,
+            temperature: 0.25,
+            checksum: 0xffff,
+        }
+    );
+    let newer = v2::Reading {
+        seq: 5,
+        temperature: 0.5,
+        checksum: 9,
+    }
+    .to_vec()
+    .unwrap();
+    assert_eq!(Reading::from_slice(&newer).unwrap().0.checksum, 9);
+    let current = Reading {
+        seq: 6,
+        temperature: 0.1,
+        checksum: 1,
+    };
+    assert_eq!(
+        Reading::from_slice(&current.to_vec().unwrap()).unwrap().0,
+        current
+    );
+}
+
+#[test]
+fn truncated_versioned_records() {
+    let bytes = v2::Reading {
+        seq: 1,
+        temperature: 2.0,
+        checksum: 3,
+    }
+    .to_vec()
+    .unwrap();
+    assert!(v2::Reading::from_slice(&[]).unwrap_err().is_eof());
+    for len in 1..bytes.len() {
+        assert!(matches!(
+            v2::Reading::from_slice(&bytes[..len]),
+            Err(BinaryIoError::Truncated)
+        ));
+    }
+    // a header claiming a body shorter than the fields of its own version
+    assert!(matches!(
+        v2::Reading::from_slice(&[2, 0, 4, 0, 0, 0, 1, 0, 0, 0]),
+        Err(BinaryIoError::Truncated)
+    ));
+    assert!(Event::from_slice(&[]).unwrap_err().is_eof());
+    assert_eq!(
+        Event::from_slice(b"EV\x01\x00\x01\x00\x00\x00\x00").unwrap().0,
+        Event::Start
+    );
+}
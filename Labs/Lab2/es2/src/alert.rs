@@ -0,0 +1,270 @@
+use crate::window::{format_seconds, parse_seconds};
+use crate::{SensorData, SensorDataError, WindowStats};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Metric {
+    // of the newest sample, as the consumer prints them
+    Avg,
+    Min,
+    Max,
+    // of the window
+    Mean,
+    Stddev,
+    Percentile(f32),
+    Rate,
+}
+
+impl Metric {
+    // None when the metric is not known yet, as the rate of a single sample
+    pub fn value(self, sample: &SensorData, stats: &WindowStats) -> Option<f32> {
+        Some(match self {
+            Metric::Avg => sample.avg(),
+            Metric::Min => sample.min(),
+            Metric::Max => sample.max(),
+            Metric::Mean => stats.mean,
+            Metric::Stddev => stats.stddev,
+            Metric::Percentile(p) => stats.percentile(p),
+            Metric::Rate => stats.rate?,
+        })
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "avg" => Metric::Avg,
+            "min" => Metric::Min,
+            "max" => Metric::Max,
+            "mean" => Metric::Mean,
+            "stddev" => Metric::Stddev,
+            "rate" => Metric::Rate,
+            _ => match text.strip_prefix('p').map(str::parse::<f32>) {
+                Some(Ok(p)) if (0.0..=100.0).contains(&p) => Metric::Percentile(p),
+                _ => return Err(format!("unknown metric {:?}", text)),
+            },
+        })
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Avg => write!(f, "avg"),
+            Metric::Min => write!(f, "min"),
+            Metric::Max => write!(f, "max"),
+            Metric::Mean => write!(f, "mean"),
+            Metric::Stddev => write!(f, "stddev"),
+            Metric::Percentile(p) => write!(f, "p{}", p),
+            Metric::Rate => write!(f, "rate"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    fn holds(self, value: f32, threshold: f32) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            _ => return Err(format!("unknown comparison {:?}", text)),
+        })
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+        })
+    }
+}
+
+// `sensor <seq|*> <metric> <comparison> <threshold> [for <duration>]`, as
+// `sensor 3 avg > 0.9 for 30s`. the condition has to hold for every sample of the sensor over the
+// duration, measured with the sample timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    // None for every sensor
+    pub sensor: Option<u32>,
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: f32,
+    pub duration: u32,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = text.split_whitespace().collect();
+        let (condition, duration) = match words.as_slice() {
+            [condition @ .., "for", duration] => (
+                condition,
+                parse_seconds(duration)
+                    .ok_or_else(|| format!("invalid duration {:?}", duration))?,
+            ),
+            condition => (condition, 0),
+        };
+        let ["sensor", sensor, metric, comparison, threshold] = condition else {
+            return Err(
+                "expected sensor <seq|*> <metric> <comparison> <threshold> [for <duration>]"
+                    .to_string(),
+            );
+        };
+        let sensor = match *sensor {
+            "*" => None,
+            seq => Some(
+                seq.parse()
+                    .map_err(|_| format!("invalid sensor {:?}", seq))?,
+            ),
+        };
+        Ok(Rule {
+            sensor,
+            metric: metric.parse()?,
+            comparison: comparison.parse()?,
+            threshold: threshold
+                .parse()
+                .map_err(|_| format!("invalid threshold {:?}", threshold))?,
+            duration,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sensor {
+            Some(seq) => write!(f, "sensor {}", seq)?,
+            None => write!(f, "sensor *")?,
+        }
+        write!(f, " {} {} {}", self.metric, self.comparison, self.threshold)?;
+        if self.duration > 0 {
+            write!(f, " for {}", format_seconds(self.duration))?;
+        }
+        Ok(())
+    }
+}
+
+// one rule per line, empty lines and lines starting with # are skipped
+pub fn load_rules(reader: impl BufRead) -> Result<Vec<Rule>, SensorDataError> {
+    let mut rules = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let rule = line
+            .parse()
+            .map_err(|e| SensorDataError::ConfigError(format!("line {}: {}", i + 1, e)))?;
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+// printed as a json line by the consumer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub state: AlertState,
+    pub sensor: u32,
+    pub value: Option<f32>,
+    // timestamp of the first sample the condition held for
+    pub since: u32,
+    pub timestamp: u32,
+}
+
+#[derive(Default)]
+struct Condition {
+    since: Option<u32>,
+    firing: bool,
+}
+
+pub struct Alerts {
+    rules: Vec<Rule>,
+    // by rule index and sensor
+    conditions: HashMap<(usize, u32), Condition>,
+}
+
+impl Alerts {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Alerts {
+            rules,
+            conditions: HashMap::new(),
+        }
+    }
+
+    // evaluates the rules of the sensor of `sample`, `stats` being its window with the sample in it
+    pub fn check(&mut self, sample: &SensorData, stats: &WindowStats) -> Vec<AlertEvent> {
+        let mut events = vec![];
+        let seq = sample.seq();
+        let now = sample.timestamp();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.sensor.is_some_and(|s| s != seq) {
+                continue;
+            }
+            let value = rule.metric.value(sample, stats);
+            let holds = value.is_some_and(|v| rule.comparison.holds(v, rule.threshold));
+            let condition = self.conditions.entry((index, seq)).or_default();
+            let event = |state, since| AlertEvent {
+                rule: rule.to_string(),
+                state,
+                sensor: seq,
+                value,
+                since,
+                timestamp: now,
+            };
+            if holds {
+                let since = *condition.since.get_or_insert(now);
+                if !condition.firing && now.saturating_sub(since) >= rule.duration {
+                    condition.firing = true;
+                    events.push(event(AlertState::Firing, since));
+                }
+            } else if let Some(since) = condition.since.take() {
+                if condition.firing {
+                    condition.firing = false;
+                    events.push(event(AlertState::Resolved, since));
+                }
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,542 @@
+use crate::source::CsvSource;
+use crate::{SensorData, SensorDataError, SensorSource};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// segments are named after the timestamp of their first sample, as sensors-1700000000.csv.gz,
+// with a counter when several segments start in the same second
+const SEGMENT_PREFIX: &str = "sensors-";
+// the format of CsvSource, so that an archive can be replayed by the producer
+pub const CSV_HEADER: &str = "timestamp,seq,val0,val1,val2,val3,val4,val5,val6,val7,val8,val9";
+const HOUR: u32 = 3600;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    #[default]
+    Csv,
+    /// needs the parquet feature
+    // rows are kept in memory until a row group is full and a segment is only readable once
+    // closed, so a consumer that is killed loses what it did not write yet
+    Parquet,
+}
+
+// when the segment being written is closed and a new one started
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    // once the segment reaches this many bytes, checked after every flush
+    Size(u64),
+    // when a sample belongs to another hour than the first one of the segment
+    Hourly,
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text == "hourly" {
+            return Ok(Rotation::Hourly);
+        }
+        let (digits, unit) = match text.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+            Some((i, _)) => text.split_at(i),
+            None => (text, ""),
+        };
+        let unit = match unit {
+            "" => 1,
+            "K" | "k" => 1 << 10,
+            "M" | "m" => 1 << 20,
+            "G" | "g" => 1 << 30,
+            _ => return Err(format!("invalid rotation {:?}, as 64M or hourly", text)),
+        };
+        match digits.parse::<u64>() {
+            Ok(size) if size > 0 => Ok(Rotation::Size(size * unit)),
+            _ => Err(format!("invalid rotation {:?}, as 64M or hourly", text)),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rotation::Hourly => write!(f, "hourly"),
+            Rotation::Size(size) => write!(f, "{}", size),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ArchiveArgs {
+    /// writes every consumed sample to rotating segments in this directory
+    #[clap(long)]
+    pub archive: Option<PathBuf>,
+    #[clap(long, value_enum, default_value_t)]
+    pub archive_format: ArchiveFormat,
+    /// a size as 64M, or hourly
+    #[clap(long, default_value = "hourly")]
+    pub archive_rotate: Rotation,
+    /// gzip csv segments, or compress parquet columns with gzip
+    #[clap(long)]
+    pub archive_gzip: bool,
+    /// the oldest segments are removed beyond this many
+    #[clap(long)]
+    pub archive_keep: Option<usize>,
+}
+
+impl ArchiveArgs {
+    // None without --archive
+    pub fn open(&self) -> Result<Option<Archive>, SensorDataError> {
+        let Some(dir) = &self.archive else {
+            return Ok(None);
+        };
+        Archive::new(
+            dir,
+            self.archive_format,
+            self.archive_rotate,
+            self.archive_gzip,
+            self.archive_keep,
+        )
+        .map(Some)
+    }
+}
+
+// a sample as a line of CSV_HEADER, the values read back exactly
+pub fn write_csv_line(writer: &mut dyn Write, sample: &SensorData) -> std::io::Result<()> {
+    write!(writer, "{},{}", sample.timestamp(), sample.seq())?;
+    for value in sample.values() {
+        write!(writer, ",{}", value)?;
+    }
+    writeln!(writer)
+}
+
+fn segment_name(first: u32, counter: u32, extension: &str) -> String {
+    match counter {
+        0 => format!("{}{}.{}", SEGMENT_PREFIX, first, extension),
+        n => format!("{}{}-{}.{}", SEGMENT_PREFIX, first, n, extension),
+    }
+}
+
+// the first timestamp and the counter of a segment name
+type SegmentKey = (u32, u32);
+
+fn parse_segment_name(name: &str) -> Option<SegmentKey> {
+    let rest = name.strip_prefix(SEGMENT_PREFIX)?;
+    let (stem, extension) = rest.split_once('.')?;
+    if !["csv", "csv.gz", "parquet"].contains(&extension) {
+        return None;
+    }
+    match stem.split_once('-') {
+        Some((first, counter)) => Some((first.parse().ok()?, counter.parse().ok()?)),
+        None => Some((stem.parse().ok()?, 0)),
+    }
+}
+
+fn named_segments(dir: &Path) -> Result<Vec<(SegmentKey, PathBuf)>, SensorDataError> {
+    let mut segments = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if let Some(key) = parse_segment_name(name) {
+            segments.push((key, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+// the segments of an archive, oldest first
+pub fn segments(dir: &Path) -> Result<Vec<PathBuf>, SensorDataError> {
+    Ok(named_segments(dir)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
+
+// the segments that can hold samples from `since` included to `until` excluded, found from their
+// names alone. a segment ends where the next one starts, which holds as long as the samples were
+// archived in timestamp order, as the producer stamps them
+pub fn segments_between(
+    dir: &Path,
+    since: Option<u32>,
+    until: Option<u32>,
+) -> Result<Vec<PathBuf>, SensorDataError> {
+    let segments = named_segments(dir)?;
+    let starts: Vec<u32> = segments.iter().map(|((first, _), _)| *first).collect();
+    Ok(segments
+        .into_iter()
+        .enumerate()
+        .filter(|(i, ((first, _), _))| {
+            let starts_after = until.is_some_and(|t| *first >= t);
+            let ends_before = since.is_some_and(|t| starts.get(i + 1).is_some_and(|&n| n < t));
+            !starts_after && !ends_before
+        })
+        .map(|(_, (_, path))| path)
+        .collect())
+}
+
+// calls `f` with every sample of a segment. a gzip segment that was not finished, as the one a
+// consumer is still writing, is read up to its last flush
+pub fn read_segment(
+    path: &Path,
+    mut f: impl FnMut(SensorData) -> Result<(), SensorDataError>,
+) -> Result<(), SensorDataError> {
+    let name = path.to_string_lossy();
+    if name.ends_with(".parquet") {
+        return parquet_segment::read(path, f);
+    }
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn BufRead> = match name.ends_with(".gz") {
+        true => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        false => Box::new(file),
+    };
+    let mut source = CsvSource::new(reader);
+    loop {
+        match source.next_sample() {
+            Ok(Some(sample)) => f(sample)?,
+            Ok(None) => return Ok(()),
+            Err(SensorDataError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+enum Writer {
+    Csv(BufWriter<File>),
+    CsvGz(GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet_segment::Writer),
+}
+
+struct Segment {
+    path: PathBuf,
+    hour: u32,
+    writer: Writer,
+}
+
+impl Segment {
+    fn write(&mut self, sample: &SensorData) -> Result<(), SensorDataError> {
+        let writer: &mut dyn Write = match &mut self.writer {
+            Writer::Csv(w) => w,
+            Writer::CsvGz(w) => w,
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(w) => return w.write(sample),
+        };
+        write_csv_line(writer, sample)?;
+        Ok(())
+    }
+
+    // the size of the segment so far, estimated for parquet
+    fn flush(&mut self) -> Result<u64, SensorDataError> {
+        match &mut self.writer {
+            Writer::Csv(w) => w.flush()?,
+            Writer::CsvGz(w) => w.flush()?,
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(w) => return w.flush(),
+        }
+        Ok(std::fs::metadata(&self.path)?.len())
+    }
+
+    fn close(self) -> Result<(), SensorDataError> {
+        match self.writer {
+            Writer::Csv(mut w) => w.flush()?,
+            Writer::CsvGz(w) => w.finish()?.flush()?,
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(w) => w.close()?,
+        }
+        Ok(())
+    }
+}
+
+// where the consumer writes the samples it read, as they are gone from the ring afterwards
+pub struct Archive {
+    dir: PathBuf,
+    format: ArchiveFormat,
+    rotation: Rotation,
+    gzip: bool,
+    keep: Option<usize>,
+    segment: Option<Segment>,
+}
+
+impl Archive {
+    pub fn new(
+        dir: &Path,
+        format: ArchiveFormat,
+        rotation: Rotation,
+        gzip: bool,
+        keep: Option<usize>,
+    ) -> Result<Self, SensorDataError> {
+        if format == ArchiveFormat::Parquet && !cfg!(feature = "parquet") {
+            return Err(SensorDataError::ConfigError(
+                "built without the parquet feature".to_string(),
+            ));
+        }
+        if keep == Some(0) {
+            return Err(SensorDataError::ConfigError(
+                "at least one segment must be kept".to_string(),
+            ));
+        }
+        std::fs::create_dir_all(dir)?;
+        Ok(Archive {
+            dir: dir.to_path_buf(),
+            format,
+            rotation,
+            gzip,
+            keep,
+            segment: None,
+        })
+    }
+
+    // the segment being written
+    pub fn current(&self) -> Option<&Path> {
+        self.segment.as_ref().map(|s| s.path.as_path())
+    }
+
+    pub fn write(&mut self, sample: &SensorData) -> Result<(), SensorDataError> {
+        let hour = sample.timestamp() / HOUR;
+        if self.rotation == Rotation::Hourly
+            && self.segment.as_ref().is_some_and(|s| s.hour != hour)
+        {
+            self.close()?;
+        }
+        if self.segment.is_none() {
+            self.segment = Some(self.create(sample.timestamp())?);
+            self.retain()?;
+        }
+        self.segment.as_mut().unwrap().write(sample)
+    }
+
+    // makes the samples written so far readable, a size rotation happens here
+    pub fn flush(&mut self) -> Result<(), SensorDataError> {
+        let Some(segment) = &mut self.segment else {
+            return Ok(());
+        };
+        let size = segment.flush()?;
+        if matches!(self.rotation, Rotation::Size(limit) if size >= limit) {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    // finishes the segment being written, the next sample starts a new one
+    pub fn close(&mut self) -> Result<(), SensorDataError> {
+        match self.segment.take() {
+            Some(segment) => segment.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn create(&self, first: u32) -> Result<Segment, SensorDataError> {
+        let extension = match (self.format, self.gzip) {
+            (ArchiveFormat::Csv, false) => "csv",
+            (ArchiveFormat::Csv, true) => "csv.gz",
+            (ArchiveFormat::Parquet, _) => "parquet",
+        };
+        let mut counter = 0;
+        let (path, file) = loop {
+            let path = self.dir.join(segment_name(first, counter, extension));
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => counter += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        let writer = match self.format {
+            ArchiveFormat::Csv => {
+                let file = BufWriter::new(file);
+                let mut writer = match self.gzip {
+                    true => Writer::CsvGz(GzEncoder::new(file, flate2::Compression::default())),
+                    false => Writer::Csv(file),
+                };
+                let header: &mut dyn Write = match &mut writer {
+                    Writer::Csv(w) => w,
+                    Writer::CsvGz(w) => w,
+                    #[cfg(feature = "parquet")]
+                    Writer::Parquet(_) => unreachable!(),
+                };
+                writeln!(header, "{}", CSV_HEADER)?;
+                writer
+            }
+            #[cfg(feature = "parquet")]
+            ArchiveFormat::Parquet => {
+                Writer::Parquet(parquet_segment::Writer::new(file, self.gzip)?)
+            }
+            #[cfg(not(feature = "parquet"))]
+            ArchiveFormat::Parquet => unreachable!("checked by Archive::new"),
+        };
+        Ok(Segment {
+            path,
+            hour: first / HOUR,
+            writer,
+        })
+    }
+
+    // removes the oldest segments beyond the retention limit, never the one being written
+    fn retain(&self) -> Result<(), SensorDataError> {
+        let Some(keep) = self.keep else {
+            return Ok(());
+        };
+        let current = self.current();
+        let old: Vec<_> = segments(&self.dir)?
+            .into_iter()
+            .filter(|path| Some(path.as_path()) != current)
+            .collect();
+        for path in old.iter().take((old.len() + 1).saturating_sub(keep)) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Archive {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            eprintln!("Error closing the archive: {:?}", e);
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_segment {
+    use super::{SensorData, SensorDataError};
+    use crate::SENSOR_DATA_SIZE;
+    use parquet::basic::{Compression, GzipLevel};
+    use parquet::data_type::{FloatType, Int32Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::SerializedFileReader;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::record::RowAccessor;
+    use parquet::schema::parser::parse_message_type;
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    // samples are kept until a row group is full, a segment can only be read once closed
+    const ROW_GROUP: usize = 1024;
+
+    const SCHEMA: &str = "message sensor_data {
+        required int32 timestamp (INTEGER(32, false));
+        required int32 seq (INTEGER(32, false));
+        required float val0; required float val1; required float val2; required float val3;
+        required float val4; required float val5; required float val6; required float val7;
+        required float val8; required float val9;
+    }";
+
+    fn archive_error(e: ParquetError) -> SensorDataError {
+        SensorDataError::ArchiveError(e.to_string())
+    }
+
+    pub(super) struct Writer {
+        writer: SerializedFileWriter<File>,
+        rows: Vec<SensorData>,
+    }
+
+    impl Writer {
+        pub(super) fn new(file: File, gzip: bool) -> Result<Self, SensorDataError> {
+            let schema = Arc::new(parse_message_type(SCHEMA).map_err(archive_error)?);
+            let compression = match gzip {
+                true => Compression::GZIP(GzipLevel::default()),
+                false => Compression::UNCOMPRESSED,
+            };
+            let properties = WriterProperties::builder()
+                .set_compression(compression)
+                .build();
+            let writer = SerializedFileWriter::new(file, schema, Arc::new(properties))
+                .map_err(archive_error)?;
+            Ok(Writer {
+                writer,
+                rows: Vec::with_capacity(ROW_GROUP),
+            })
+        }
+
+        pub(super) fn write(&mut self, sample: &SensorData) -> Result<(), SensorDataError> {
+            self.rows.push(*sample);
+            Ok(())
+        }
+
+        fn write_row_group(&mut self) -> Result<(), ParquetError> {
+            let mut group = self.writer.next_row_group()?;
+            let mut column = 0;
+            while let Some(mut writer) = group.next_column()? {
+                // unsigned values are stored in signed columns, as parquet does
+                match column {
+                    0 | 1 => {
+                        let values: Vec<i32> = self
+                            .rows
+                            .iter()
+                            .map(|s| [s.timestamp(), s.seq()][column] as i32)
+                            .collect();
+                        writer
+                            .typed::<Int32Type>()
+                            .write_batch(&values, None, None)?;
+                    }
+                    _ => {
+                        let values: Vec<f32> =
+                            self.rows.iter().map(|s| s.values()[column - 2]).collect();
+                        writer
+                            .typed::<FloatType>()
+                            .write_batch(&values, None, None)?;
+                    }
+                }
+                writer.close()?;
+                column += 1;
+            }
+            group.close()?;
+            self.rows.clear();
+            Ok(())
+        }
+
+        pub(super) fn flush(&mut self) -> Result<u64, SensorDataError> {
+            if self.rows.len() >= ROW_GROUP {
+                self.write_row_group().map_err(archive_error)?;
+            }
+            Ok(self.writer.bytes_written() as u64 + self.rows.len() as u64 * SENSOR_DATA_SIZE)
+        }
+
+        pub(super) fn close(mut self) -> Result<(), SensorDataError> {
+            if !self.rows.is_empty() {
+                self.write_row_group().map_err(archive_error)?;
+            }
+            self.writer.close().map_err(archive_error)?;
+            Ok(())
+        }
+    }
+
+    pub(super) fn read(
+        path: &Path,
+        mut f: impl FnMut(SensorData) -> Result<(), SensorDataError>,
+    ) -> Result<(), SensorDataError> {
+        let reader = SerializedFileReader::new(File::open(path)?).map_err(archive_error)?;
+        for row in reader {
+            let row = row.map_err(archive_error)?;
+            let mut values = [0.0; 10];
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = row.get_float(i + 2).map_err(archive_error)?;
+            }
+            let timestamp = row.get_uint(0).map_err(archive_error)?;
+            let seq = row.get_uint(1).map_err(archive_error)?;
+            f(SensorData::new(seq, values, timestamp))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+mod parquet_segment {
+    use super::{SensorData, SensorDataError};
+    use std::path::Path;
+
+    pub(super) fn read(
+        _path: &Path,
+        _f: impl FnMut(SensorData) -> Result<(), SensorDataError>,
+    ) -> Result<(), SensorDataError> {
+        Err(SensorDataError::ConfigError(
+            "built without the parquet feature".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,82 @@
+use binary_io::inspect::{inspect, Layout};
+use binary_io::Endian;
+use clap::Parser;
+use sensors::schema_registry;
+
+#[derive(Parser)]
+struct InspectArgs {
+    #[clap(default_value = "sensor_data.bin")]
+    file: String,
+    /// schema of the records
+    #[clap(long, default_value = "sensor-data")]
+    schema: String,
+    /// schema of the header in front of the records
+    #[clap(long, default_value = "sensor-metadata-slots")]
+    header: String,
+    #[clap(long)]
+    no_header: bool,
+    /// number of records, anything after them is reported as trailing bytes
+    #[clap(long)]
+    count: Option<usize>,
+    /// overrides the byte order of schemas that do not pin one
+    #[clap(long, value_parser = ["little", "big", "native"])]
+    endian: Option<String>,
+    /// list the registered schemas and exit
+    #[clap(long)]
+    list: bool,
+}
+
+fn main() {
+    let args = InspectArgs::parse();
+    let registry = schema_registry();
+    if args.list {
+        for schema in registry.schemas() {
+            let size = match schema.size {
+                Some(size) => format!("{} bytes", size),
+                None => "variable size".to_string(),
+            };
+            println!("{:<16} {} ({})", schema.name, schema.type_name, size);
+        }
+        return;
+    }
+    let lookup = |name: &str| {
+        registry.get(name).unwrap_or_else(|| {
+            eprintln!("Unknown schema {}, see --list", name);
+            std::process::exit(2);
+        })
+    };
+    let layout = Layout {
+        header: (!args.no_header).then(|| lookup(&args.header)),
+        record: lookup(&args.schema),
+        count: args.count,
+        endian: args.endian.as_deref().map(|e| match e {
+            "little" => Endian::Little,
+            "big" => Endian::Big,
+            _ => Endian::NATIVE,
+        }),
+    };
+    let bytes = match std::fs::read(&args.file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error: cannot read {}: {}", args.file, e);
+            std::process::exit(1);
+        }
+    };
+    let report = match inspect(&mut std::io::stdout().lock(), &bytes, &layout) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "{} records, {} undecodable, {} truncated bytes, {} trailing bytes",
+        report.records,
+        report.errors,
+        report.truncated.map_or(0, |(_, len)| len),
+        report.trailing.map_or(0, |(_, len)| len)
+    );
+    if report.errors > 0 || report.truncated.is_some() || report.trailing.is_some() {
+        std::process::exit(1);
+    }
+}
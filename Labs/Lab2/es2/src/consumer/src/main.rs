@@ -1,69 +1,182 @@
 use clap::Parser;
-use sensors::{sensor_lock_file, sensor_unlock_file, SensorData, SensorFileMetadata, Args, SensorDataError};
-use std::{
-    fs::OpenOptions,
-    io::{Seek, Write},
+use sensors::{
+    load_rules, Alerts, Archive, ArchiveArgs, Args, Client, Endpoint, FileNotifier, Metrics,
+    SensorData, SensorDataError, SensorWindows, Window, FALLBACK_WAIT,
 };
-use binary_io::BinPack;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+
+#[derive(Parser)]
+struct ConsumerArgs {
+    #[clap(flatten)]
+    args: Args,
+    /// rolling statistics of every sensor over a number of samples or a duration, as 30s or 5m
+    #[clap(long, default_value = "60s")]
+    window: Window,
+    /// prints the rolling statistics of every sample read
+    #[clap(long)]
+    stats: bool,
+    /// alert rules, one per line as `sensor 3 avg > 0.9 for 30s`
+    #[clap(long)]
+    alerts: Option<String>,
+    /// file the alerts are appended to, instead of stdout
+    #[clap(long, requires = "alerts")]
+    alerts_out: Option<String>,
+    /// reads from a sensors-server instead of the file, `unix:<path>` or a tcp address
+    #[clap(long)]
+    remote: Option<Endpoint>,
+    /// skips the unread samples older than this timestamp first
+    #[clap(long, requires = "remote")]
+    since: Option<u32>,
+    #[clap(flatten)]
+    archive: ArchiveArgs,
+}
+
+struct Report {
+    windows: SensorWindows,
+    alerts: Alerts,
+    // json lines, kept apart from the human readable report
+    alerts_out: Box<dyn Write>,
+    // the human readable report, on stderr when the alerts are on stdout
+    out: Box<dyn Write>,
+    window: Window,
+    stats: bool,
+    archive: Option<Archive>,
+}
+
+impl Report {
+    fn sample(&mut self, i: usize, sensor: &SensorData) -> Result<(), SensorDataError> {
+        writeln!(self.out, "Sensor {}: min => {:.06}, max => {:.06}, avg => {:.06}", i, sensor.min(), sensor.max(), sensor.avg())?;
+        let window_stats = self.windows.push(sensor);
+        if self.stats {
+            writeln!(self.out, "Sensor {} over {}: {}", sensor.seq(), self.window, window_stats)?;
+        }
+        for event in self.alerts.check(sensor, &window_stats) {
+            serde_json::to_writer(&mut self.alerts_out, &event).map_err(std::io::Error::from)?;
+            writeln!(self.alerts_out)?;
+        }
+        match &mut self.archive {
+            Some(archive) => archive.write(sensor),
+            None => Ok(()),
+        }
+    }
+
+    // the samples reported so far are in the archive
+    fn flush(&mut self) -> Result<(), SensorDataError> {
+        self.alerts_out.flush()?;
+        match &mut self.archive {
+            Some(archive) => archive.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// every sample is acknowledged once reported and archived, a sample is reported again only if the
+// consumer dies in between
+fn consume_remote(
+    endpoint: &Endpoint,
+    args: &Args,
+    since: Option<u32>,
+    report: &mut Report,
+    metrics: &Metrics,
+) -> Result<(), SensorDataError> {
+    let mut client = Client::connect(endpoint)?;
+    let unread = client.subscribe(&args.name)?;
+    writeln!(report.out, "Subscribed to {} as {}, {} unread samples", endpoint, args.name, unread)?;
+    if let Some(timestamp) = since {
+        writeln!(report.out, "Skipped {} samples", client.seek(timestamp)?)?;
+    }
+    while let Some(sample) = client.next_sample()? {
+        if args.verbose {
+            writeln!(report.out, "Read data {:?}", sample)?;
+        }
+        report.sample(sample.seq() as usize, &sample)?;
+        report.flush()?;
+        client.ack(1)?;
+        metrics.consumed(&args.name, &[sample], None);
+    }
+    writeln!(report.out, "Server closed the connection")?;
+    Ok(())
+}
 
 fn main() -> Result<(), SensorDataError> {
-    let args = Args::parse();
-    let file;
+    let ConsumerArgs {
+        args,
+        window,
+        stats,
+        alerts,
+        alerts_out,
+        remote,
+        since,
+        archive,
+    } = ConsumerArgs::parse();
+    // the alerts take stdout unless they are redirected, the report moves out of their way
+    let report_on_stderr = alerts.is_some() && alerts_out.is_none();
+    let alerts = match alerts {
+        Some(path) => Alerts::new(load_rules(BufReader::new(File::open(path)?))?),
+        None => Alerts::new(vec![]),
+    };
+    let alerts_out: Box<dyn Write> = match alerts_out {
+        Some(path) => Box::new(BufWriter::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        )),
+        None => Box::new(std::io::stdout()),
+    };
+    let out: Box<dyn Write> = match report_on_stderr {
+        true => Box::new(std::io::stderr()),
+        false => Box::new(std::io::stdout()),
+    };
+    let mut report = Report {
+        windows: SensorWindows::new(window),
+        alerts,
+        alerts_out,
+        out,
+        window,
+        stats,
+        archive: archive.open()?,
+    };
+    let metrics = args.open_metrics()?;
+    if let Some(endpoint) = remote {
+        return consume_remote(&endpoint, &args, since, &mut report, &metrics);
+    }
+    let ring;
     loop {
-        match OpenOptions::new()
-            .write(true)
-            .read(true)
-            .open(&args.file)
-        {
-            Ok(f) => {
-                file = f;
+        match args.open_ring(false) {
+            Ok(r) => {
+                ring = r;
                 break;
             }
             Err(err) => {
-                println!("Error: {}", err);
-                std::thread::sleep(std::time::Duration::from_millis(50)); // wait 50 ms and try opening the file again
+                writeln!(report.out, "Error: {:?}", err)?;
+                std::thread::sleep(std::time::Duration::from_millis(50)); // wait 50 ms and try opening the ring again
             }
         };
     }
-    const SENSOR_DATA_SIZE: u64 = std::mem::size_of::<SensorData>() as u64;
-    const METADATA_SIZE: u64 = std::mem::size_of::<SensorFileMetadata>() as u64;
+    // woken up by the producer as soon as it writes a sample
+    let notifier = FileNotifier::watch(&args.ring_path())?;
     loop {
-        sensor_lock_file(&file)?;
-        // read data from file
-        let mut reader = std::io::BufReader::new(&file);
-        let mut writer = std::io::BufWriter::new(&file);
-        // always read metadata from start of file.
-        reader.rewind()?;
-        let mut metadata = SensorFileMetadata::from_bytes(&mut reader).ok_or(SensorDataError::MetadataReadError)?;
-        println!("Read metadata {:?}", metadata);
-        let mut data = vec![];
-        for _ in 0..args.sensors {
-            let offset = metadata.read_head * SENSOR_DATA_SIZE + METADATA_SIZE;
-            reader.seek(std::io::SeekFrom::Start(offset))?;
-            let d = SensorData::from_bytes(&mut reader).ok_or(SensorDataError::DataReadError)?;
-            if args.verbose {
-                println!("Read data {:?}", d);
+        notifier.clear();
+        let (data, metadata) = match metrics.consume(&*ring, &args.name, args.sensors as usize) {
+            Ok((data, Some(metadata))) if !data.is_empty() => (data, metadata),
+            Err(SensorDataError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                let name = &args.name;
+                writeln!(report.out, "Consumer {} is not registered, see sensors-consumers", name)?;
+                std::process::exit(1);
             }
-            data.push(d);
-            match metadata.advance_read_head() {
-                Ok(_) => (),
-                Err(e) => {
-                    println!("Error: {}", e);
-                    break;
-                }
+            Err(e) => return Err(e),
+            // nothing to read, and nothing was written back either: our own write would wake us up
+            Ok(_) => {
+                notifier.wait(FALLBACK_WAIT);
+                continue;
             }
-            if metadata.is_empty() {
-                break;
-            }
-        }
+        };
         for (i, sensor) in data.iter().enumerate() {
-            println!("Sensor {}: min => {:.06}, max => {:.06}, avg => {:.06}", i, sensor.min(), sensor.max(), sensor.avg());
+            if args.verbose {
+                writeln!(report.out, "Read data {:?}", sensor)?;
+            }
+            report.sample(i, sensor)?;
         }
-        println!("After reading: {:?}", metadata);
-        metadata.to_bytes(&mut writer)?;
-        reader.rewind()?;
-        writer.flush()?;
-        sensor_unlock_file(&file)?;
-        std::thread::sleep(std::time::Duration::from_millis(10_000));
+        report.flush()?;
+        writeln!(report.out, "After reading: {:?}", metadata)?;
     }
 }
@@ -0,0 +1,291 @@
+use crate::{
+    baseline_metadata, decode_metadata_slots, name_bytes, record_offset, sensor_lock_file,
+    sensor_unlock_file, write_metadata, BaselineSample, Cursor, FullPolicy, SensorData,
+    SensorDataError, SensorFileMetadata, SensorRing, BASELINE_METADATA_SIZE, BASELINE_SAMPLE_SIZE,
+    DATA_OFFSET, DEFAULT_CONSUMER, METADATA_SLOT_SIZE, SENSOR_DATA_SIZE,
+};
+use binary_io::{BinPack, Endian};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct Finding {
+    pub message: String,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub findings: Vec<Finding>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn all_repaired(&self) -> bool {
+        self.findings.iter().all(|f| f.repaired)
+    }
+
+    fn found(&mut self, message: String, repaired: bool) {
+        self.findings.push(Finding { message, repaired });
+    }
+}
+
+pub struct FsckOptions {
+    pub repair: bool,
+    // buffer size to rebuild the metadata with when none can be read, by default it is guessed
+    // from the number of records in the file
+    pub samples: Option<u64>,
+}
+
+fn read_header(file: &File) -> Result<Vec<u8>, SensorDataError> {
+    let mut reader = BufReader::new(file);
+    reader.rewind()?;
+    let mut header = vec![];
+    reader.take(DATA_OFFSET).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+fn read_record(file: &File, index: u64) -> Result<SensorData, SensorDataError> {
+    let mut reader = BufReader::new(file);
+    reader.seek(std::io::SeekFrom::Start(record_offset(index)))?;
+    SensorData::from_bytes(&mut reader).map_err(SensorDataError::DataReadError)
+}
+
+// checks a locked sensor file: both metadata slots, the consistency of the metadata with itself and
+// with the file, and every sample a consumer has not read yet. with `repair` whatever can be fixed
+// is, at the cost of the samples that cannot be read anymore
+pub fn fsck(file: &File, options: &FsckOptions) -> Result<FsckReport, SensorDataError> {
+    let mut report = FsckReport::default();
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(report);
+    }
+    let header = read_header(file)?;
+    let slots = decode_metadata_slots(&header);
+    if slots.iter().all(Result::is_err) && baseline_metadata(&header, len).is_some() {
+        // replacing the file is up to `upgrade_baseline`, which needs its path
+        report.found(
+            "the file has the layout of the first release, sensors-fsck --repair upgrades it"
+                .to_string(),
+            false,
+        );
+        return Ok(report);
+    }
+
+    let mut changed = false;
+    let newest = slots
+        .iter()
+        .enumerate()
+        .filter_map(|(i, slot)| slot.as_ref().ok().map(|m| (i, *m)))
+        .max_by_key(|(_, m)| m.generation());
+    let mut metadata = match newest {
+        Some((current, metadata)) => {
+            let other = 1 - current;
+            let start = (other as u64 * METADATA_SLOT_SIZE) as usize;
+            let end = (start + METADATA_SLOT_SIZE as usize).min(header.len());
+            // the other slot has not been written yet, as after the first metadata write
+            let never_written = metadata.generation() == 1
+                && header
+                    .get(start..end)
+                    .is_none_or(|s| s.iter().all(|&b| b == 0));
+            if let Err(e) = &slots[other] {
+                if !never_written {
+                    report.found(
+                        format!(
+                            "metadata slot {} is damaged ({}), probably by a torn write",
+                            other, e
+                        ),
+                        options.repair,
+                    );
+                    changed = true;
+                }
+            }
+            metadata
+        }
+        None if header.iter().all(|&b| b == 0) && len <= DATA_OFFSET => return Ok(report),
+        None => {
+            let records = len.saturating_sub(DATA_OFFSET) / SENSOR_DATA_SIZE;
+            let size = options.samples.unwrap_or(records);
+            if size == 0 {
+                report.found(
+                    "no metadata slot can be read and the buffer size is unknown, pass --samples"
+                        .to_string(),
+                    false,
+                );
+                return Ok(report);
+            }
+            report.found(
+                format!(
+                    "no metadata slot can be read, rebuilt for {} samples with only the default consumer",
+                    size
+                ),
+                options.repair,
+            );
+            changed = true;
+            SensorFileMetadata::from_size(size)
+        }
+    };
+
+    if metadata.buffer_size() == 0 || metadata.write_head >= metadata.buffer_size() {
+        let size = options.samples.unwrap_or(metadata.buffer_size());
+        if size == 0 {
+            report.found(
+                "the buffer size is 0, pass --samples to rebuild the metadata".to_string(),
+                false,
+            );
+            return Ok(report);
+        }
+        report.found(
+            format!(
+                "write head {} does not fit a buffer of {} samples, every cursor is reset",
+                metadata.write_head,
+                metadata.buffer_size()
+            ),
+            options.repair,
+        );
+        metadata = metadata.reset(size);
+        changed = true;
+    }
+
+    let buffer_size = metadata.buffer_size();
+    let names: Vec<String> = metadata.cursors().map(|c| c.name().into_owned()).collect();
+    for name in names {
+        let cursor = *metadata.cursor(&name).unwrap();
+        if cursor.read_head >= buffer_size
+            || cursor.len() > buffer_size
+            || (cursor.read_head + cursor.len()) % buffer_size != metadata.write_head
+        {
+            report.found(
+                format!(
+                    "cursor {} (read head {}, {} unread) does not end at the write head {}, its unread samples are dropped",
+                    name,
+                    cursor.read_head,
+                    cursor.len(),
+                    metadata.write_head
+                ),
+                options.repair,
+            );
+            metadata.reset_cursor(&name)?;
+            changed = true;
+            continue;
+        }
+        // a consumer can only resume after the last sample it cannot read
+        let mut bad = None;
+        for k in 0..cursor.len() {
+            let index = (cursor.read_head + k) % buffer_size;
+            if record_offset(index) + SENSOR_DATA_SIZE > len {
+                bad = Some((k, index, "is missing".to_string()));
+            } else if let Err(e) = read_record(file, index) {
+                bad = Some((k, index, format!("cannot be read ({:?})", e)));
+            }
+        }
+        if let Some((k, index, problem)) = bad {
+            report.found(
+                format!(
+                    "cursor {}: unread sample {} {}, {} samples dropped",
+                    name,
+                    index,
+                    problem,
+                    k + 1
+                ),
+                options.repair,
+            );
+            metadata.skip(&name, k + 1)?;
+            changed = true;
+        }
+    }
+
+    let records_len = len.saturating_sub(DATA_OFFSET);
+    let max_len =
+        DATA_OFFSET + (records_len / SENSOR_DATA_SIZE).min(buffer_size) * SENSOR_DATA_SIZE;
+    if len > max_len {
+        report.found(
+            format!(
+                "{} bytes after the last record, from a torn write or a larger buffer",
+                len - max_len
+            ),
+            options.repair,
+        );
+        if options.repair {
+            file.set_len(max_len)?;
+        }
+    }
+
+    if changed && options.repair {
+        // both slots end up with the repaired metadata
+        write_metadata(file, &mut metadata)?;
+        write_metadata(file, &mut metadata)?;
+    }
+    Ok(report)
+}
+
+// rewrites a file in the layout of the first release into a copy next to it, which then replaces it
+// by a rename: a crash leaves either the old file or the upgraded one. processes that still have
+// the old file open do not see the new one, they should be stopped first. false if the file did
+// not need an upgrade
+pub fn upgrade_baseline(path: &Path) -> Result<bool, SensorDataError> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    sensor_lock_file(&file)?;
+    let upgraded = upgrade_locked(path, &file);
+    sensor_unlock_file(&file)?;
+    upgraded
+}
+
+fn upgrade_locked(path: &Path, file: &File) -> Result<bool, SensorDataError> {
+    let len = file.metadata()?.len();
+    let header = read_header(file)?;
+    if decode_metadata_slots(&header).iter().any(Result::is_ok) {
+        return Ok(false);
+    }
+    let Some(old) = baseline_metadata(&header, len) else {
+        return Ok(false);
+    };
+    let mut metadata = SensorFileMetadata::from_size(old.buffer_size);
+    metadata.write_head = old.write_head;
+    metadata.policy = FullPolicy::DropNewest;
+    // the single reader of the first release becomes the default consumer
+    metadata.cursors[0] = Cursor::new(
+        name_bytes(DEFAULT_CONSUMER),
+        old.read_head,
+        old.current_size,
+    );
+
+    let name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let upgraded = path.with_file_name(format!("{}.upgrade", name));
+    let copy = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&upgraded)?;
+    let mut reader = BufReader::new(file);
+    reader.seek(std::io::SeekFrom::Start(BASELINE_METADATA_SIZE))?;
+    for index in 0..(len - BASELINE_METADATA_SIZE) / BASELINE_SAMPLE_SIZE {
+        let sample = BaselineSample::from_bytes_endian(&mut reader, Endian::NATIVE)
+            .map_err(SensorDataError::DataReadError)?;
+        copy.store_record(index, &sample.into())?;
+    }
+    // both slots, as a repair leaves them
+    write_metadata(&copy, &mut metadata)?;
+    write_metadata(&copy, &mut metadata)?;
+    copy.sync_all()?;
+    std::fs::rename(&upgraded, path)?;
+    // the rename itself is only durable once the directory is
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(true)
+}
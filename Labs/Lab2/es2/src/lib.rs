@@ -1,18 +1,67 @@
-use std::time::UNIX_EPOCH;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::time::{Duration, UNIX_EPOCH};
 
 use fs2::FileExt;
 use rand::Rng;
 
 use clap::Parser;
-use binary_io::{BinPack, BinaryIO, Write};
+use binary_io::{
+    inspect::Registry, BinPack, BinaryIO, BinaryIoError, Endian, FixedSize, FramedReader,
+    FramedWriter,
+};
+
+mod alert;
+mod archive;
+mod fsck;
+mod metrics;
+mod notification;
+mod remote;
+mod ring;
+mod server;
+mod source;
+mod window;
+pub use alert::{load_rules, AlertEvent, AlertState, Alerts, Comparison, Metric, Rule};
+pub use archive::{
+    read_segment, segments, segments_between, write_csv_line, Archive, ArchiveArgs, ArchiveFormat,
+    Rotation, CSV_HEADER,
+};
+pub use fsck::{fsck, upgrade_baseline, Finding, FsckOptions, FsckReport};
+pub use metrics::{Metrics, MetricsServer, OPENMETRICS_CONTENT_TYPE};
+pub use notification::FileNotifier;
+pub use remote::{Client, Endpoint, Request, Response, MAX_IN_FLIGHT};
+pub use ring::{
+    consume, produce, with_lock, MappedRing, MemoryRing, Produced, RingKind, SensorRing, SHM_DIR,
+};
+pub use server::Server;
+pub use source::{
+    CsvSource, LineSource, RandomSource, SensorSource, SourceArgs, SourceKind, Waveform,
+    WaveformSource,
+};
+pub use window::{SensorWindows, Window, WindowStats};
 
 #[derive(Debug)]
 pub enum SensorDataError {
     LockError(Box<dyn std::error::Error>),
     IoError(std::io::Error),
     UnlockError(Box<dyn std::error::Error>),
-    MetadataReadError,
-    DataReadError
+    MetadataReadError(BinaryIoError),
+    DataReadError(BinaryIoError),
+    WatchError(notify::Error),
+    // a sample source could not produce the next sample, as a malformed csv line
+    SourceError(String),
+    // an invalid alert rule
+    ConfigError(String),
+    // reported by a sensors-server
+    RemoteError(String),
+    // a sensors-server or client sent something that is not a message
+    ProtocolError(BinaryIoError),
+    // an archive segment could not be written or read
+    ArchiveError(String),
+    // pushed to a MemoryRing that was shut down
+    ShutDown,
 }
 
 impl From<std::io::Error> for SensorDataError {
@@ -22,7 +71,9 @@ impl From<std::io::Error> for SensorDataError {
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, BinaryIO)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, BinaryIO)]
+// version header (6 bytes) + seq + values + timestamp
+#[binary_io(endian = "little", version = 1, size = 54)]
 pub struct SensorData {
     seq: u32,
     values: [f32; 10],
@@ -30,6 +81,22 @@ pub struct SensorData {
 }
 
 impl SensorData {
+    pub fn new(seq: u32, values: [f32; 10], timestamp: u32) -> Self {
+        SensorData {
+            seq,
+            values,
+            timestamp,
+        }
+    }
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+    pub fn values(&self) -> &[f32; 10] {
+        &self.values
+    }
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
     pub fn min(&self) -> f32 {
         self.values.iter().copied().reduce(f32::min).unwrap_or(0.0f32)
     }
@@ -45,65 +112,418 @@ impl SensorData {
     }
 }
 
+// what the producer does with a sample when the buffer is full
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, BinaryIO, clap::ValueEnum)]
+#[binary_io(tag = "u8")]
+#[repr(u8)]
+pub enum FullPolicy {
+    /// wait for the consumer to make room
+    Block = 0,
+    /// the oldest unread sample is lost
+    OverwriteOldest = 1,
+    /// the new sample is lost, as files written before the policy existed do
+    #[default]
+    DropNewest = 2,
+}
+
+pub const MAX_CONSUMERS: usize = 8;
+pub const CONSUMER_NAME_LEN: usize = 16;
+// the only consumer of files written before cursors existed, registered in every new file
+pub const DEFAULT_CONSUMER: &str = "default";
+
+// read position of one consumer, a slot with an empty name is free
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, BinaryIO)]
+#[binary_io(endian = "little")]
+pub struct Cursor {
+    name: [u8; CONSUMER_NAME_LEN],
+    pub read_head: u64,
+    // samples written but not read yet by this consumer
+    len: u64,
+}
+
+impl Cursor {
+    fn new(name: [u8; CONSUMER_NAME_LEN], read_head: u64, len: u64) -> Self {
+        Cursor {
+            name,
+            read_head,
+            len,
+        }
+    }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(CONSUMER_NAME_LEN);
+        String::from_utf8_lossy(&self.name[..len])
+    }
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    fn is_free(&self) -> bool {
+        self.name[0] == 0
+    }
+}
+
+const fn name_bytes(name: &str) -> [u8; CONSUMER_NAME_LEN] {
+    let mut bytes = [0; CONSUMER_NAME_LEN];
+    let mut i = 0;
+    while i < name.len() {
+        bytes[i] = name.as_bytes()[i];
+        i += 1;
+    }
+    bytes
+}
+
+fn consumer_name(name: &str) -> Result<[u8; CONSUMER_NAME_LEN], std::io::Error> {
+    if name.is_empty() || name.len() > CONSUMER_NAME_LEN || name.contains('\0') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "consumer names must be 1 to {} bytes without NULs, not {:?}",
+                CONSUMER_NAME_LEN, name
+            ),
+        ));
+    }
+    Ok(name_bytes(name))
+}
+
+pub(crate) fn not_registered(name: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("consumer {} is not registered", name),
+    )
+}
+
 #[repr(C)]
-#[derive(Debug, Copy, Clone, BinaryIO)]
+#[derive(Debug, Copy, Clone, PartialEq, BinaryIO)]
+// version header (6 bytes) + write_head + buffer_size + policy + cursors of 32 bytes
+#[binary_io(endian = "little", version = 3, size = 279)]
 pub struct SensorFileMetadata {
-    pub read_head: u64,
     pub write_head: u64,
     buffer_size: u64,
-    current_size: u64,
+    pub policy: FullPolicy,
+    cursors: [Cursor; MAX_CONSUMERS],
+    // generation of the slot the metadata was read from, it is stored in the slot
+    #[binary_io(skip)]
+    generation: u64,
 }
 
 impl Default for SensorFileMetadata {
     fn default() -> Self {
-        SensorFileMetadata {
-            read_head: 0,
-            write_head: 0,
-            buffer_size: 20,
-            current_size: 0,
-        }
+        SensorFileMetadata::from_size(20)
     }
 }
 
 impl SensorFileMetadata {
     pub fn from_size(size: u64) -> Self {
-        SensorFileMetadata {
-            read_head: 0,
+        let mut metadata = SensorFileMetadata {
             write_head: 0,
             buffer_size: size,
-            current_size: 0,
+            policy: FullPolicy::default(),
+            cursors: Default::default(),
+            generation: 0,
+        };
+        metadata.cursors[0] = Cursor::new(name_bytes(DEFAULT_CONSUMER), 0, 0);
+        metadata
+    }
+
+    pub fn buffer_size(&self) -> u64 {
+        self.buffer_size
+    }
+
+    // bumped by every write of the metadata
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // an empty buffer of `size` samples with the same policy and consumers, for sensors-fsck
+    pub(crate) fn reset(&self, size: u64) -> Self {
+        let mut metadata = *self;
+        metadata.write_head = 0;
+        metadata.buffer_size = size;
+        for cursor in metadata.cursors.iter_mut().filter(|c| !c.is_free()) {
+            *cursor = Cursor::new(cursor.name, 0, 0);
+        }
+        metadata
+    }
+
+    // drops the unread samples of a consumer
+    pub(crate) fn reset_cursor(&mut self, name: &str) -> Result<(), std::io::Error> {
+        let write_head = self.write_head;
+        let cursor = self.cursor_mut(name)?;
+        *cursor = Cursor::new(cursor.name, write_head, 0);
+        Ok(())
+    }
+
+    // drops the `count` oldest unread samples of a consumer
+    pub(crate) fn skip(&mut self, name: &str, count: u64) -> Result<(), std::io::Error> {
+        for _ in 0..count {
+            self.advance_read_head(name)?;
         }
+        Ok(())
     }
 
+    pub fn cursors(&self) -> impl Iterator<Item = &Cursor> {
+        self.cursors.iter().filter(|c| !c.is_free())
+    }
+
+    pub fn cursor(&self, name: &str) -> Option<&Cursor> {
+        self.cursors().find(|c| c.name() == name)
+    }
+
+    // a new consumer only sees the samples written after it registered
+    pub fn register(&mut self, name: &str) -> Result<(), std::io::Error> {
+        let bytes = consumer_name(name)?;
+        if self.cursor(name).is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("consumer {} is already registered", name),
+            ));
+        }
+        let write_head = self.write_head;
+        let slot = self
+            .cursors
+            .iter_mut()
+            .find(|c| c.is_free())
+            .ok_or_else(|| {
+                std::io::Error::other(format!("no room for more than {} consumers", MAX_CONSUMERS))
+            })?;
+        *slot = Cursor::new(bytes, write_head, 0);
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Result<(), std::io::Error> {
+        let cursor = self.cursor_mut(name)?;
+        *cursor = Cursor::default();
+        Ok(())
+    }
+
+    fn cursor_mut(&mut self, name: &str) -> Result<&mut Cursor, std::io::Error> {
+        self.cursors
+            .iter_mut()
+            .find(|c| !c.is_free() && c.name() == name)
+            .ok_or_else(|| not_registered(name))
+    }
+
+    // every consumer gets the new sample. with `OverwriteOldest` a full buffer makes room by
+    // dropping the oldest sample of the consumers that are behind
     pub fn advance_write_head(&mut self) -> Result<(), std::io::Error> {
-        if self.current_size == self.buffer_size {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Buffer is full",
-            ))
-        } else {
-            self.write_head = (self.write_head + 1) % self.buffer_size;
-            self.current_size += 1;
-            Ok(())
+        if self.is_full() && self.policy != FullPolicy::OverwriteOldest {
+            return Err(std::io::Error::other("Buffer is full"));
+        }
+        let buffer_size = self.buffer_size;
+        for cursor in self.cursors.iter_mut().filter(|c| !c.is_free()) {
+            if cursor.len == buffer_size {
+                cursor.read_head = (cursor.read_head + 1) % buffer_size;
+            } else {
+                cursor.len += 1;
+            }
         }
+        self.write_head = (self.write_head + 1) % self.buffer_size;
+        Ok(())
     }
-    pub fn advance_read_head(&mut self) -> Result<(), std::io::Error> {
-        if self.current_size == 0 {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Buffer is empty",
-            ))
+    pub fn advance_read_head(&mut self, name: &str) -> Result<(), std::io::Error> {
+        let buffer_size = self.buffer_size;
+        let cursor = self.cursor_mut(name)?;
+        if cursor.len == 0 {
+            Err(std::io::Error::other("Buffer is empty"))
         } else {
-            self.read_head = (self.read_head + 1) % self.buffer_size;
-            self.current_size -= 1;
+            cursor.read_head = (cursor.read_head + 1) % buffer_size;
+            cursor.len -= 1;
             Ok(())
         }
     }
+    // nothing left to read for any consumer
     pub fn is_empty(&self) -> bool {
-        self.current_size == 0
+        self.len() == 0
+    }
+    // the slowest consumer decides when the buffer is full
+    pub fn is_full(&self) -> bool {
+        self.len() == self.buffer_size
+    }
+    // unread samples of the slowest consumer
+    pub fn len(&self) -> u64 {
+        self.cursors().map(|c| c.len).max().unwrap_or(0)
+    }
+}
+
+// sizes on disk, the version header makes them larger than size_of
+pub const SENSOR_DATA_SIZE: u64 = SensorData::ENCODED_SIZE as u64;
+pub const METADATA_SIZE: u64 = SensorFileMetadata::ENCODED_SIZE as u64;
+
+// the metadata is written to two slots in turn, each one a checksummed frame (see
+// binary_io::FramedWriter) numbered by a generation. a write torn by a crash can only damage the
+// slot being written, the other one still holds the previous metadata
+#[derive(Debug, BinaryIO)]
+#[binary_io(endian = "little")]
+struct SlotPayload {
+    generation: u64,
+    metadata: SensorFileMetadata,
+}
+
+// what a metadata slot looks like on disk, for binio-inspect
+#[derive(Debug, BinaryIO)]
+// frame length + checksum + generation + metadata
+#[binary_io(endian = "little", size = 295)]
+pub struct MetadataSlot {
+    frame_len: u32,
+    checksum: u32,
+    generation: u64,
+    metadata: SensorFileMetadata,
+}
+
+pub const METADATA_SLOT_SIZE: u64 = MetadataSlot::ENCODED_SIZE as u64;
+// the records start after both slots
+pub const DATA_OFFSET: u64 = 2 * METADATA_SLOT_SIZE;
+
+// the records found in a sensor file, and in a file of the es1 reader, for binio-inspect
+pub fn schema_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry
+        .register::<[MetadataSlot; 2]>("sensor-metadata-slots")
+        .register::<SensorFileMetadata>("sensor-metadata")
+        .register::<SensorData>("sensor-data")
+        .merge(es1::schema_registry());
+    registry
+}
+
+// how long to wait for a notification before looking at the file anyway, in case one is lost
+pub const FALLBACK_WAIT: Duration = Duration::from_secs(10);
+
+// decodes both metadata slots from the start of a file
+pub fn decode_metadata_slots(
+    header: &[u8],
+) -> [Result<SensorFileMetadata, BinaryIoError>; 2] {
+    [0, 1].map(|slot| {
+        let start = (slot * METADATA_SLOT_SIZE).min(header.len() as u64) as usize;
+        let payload = FramedReader::new(&header[start..]).read::<SlotPayload>()?;
+        let mut metadata = payload.metadata;
+        metadata.generation = payload.generation;
+        Ok(metadata)
+    })
+}
+
+// the unversioned layout of the first release: read head, write head, buffer size and current
+// size as native u64, then records of 48 bytes. sensors-fsck --repair upgrades it
+#[derive(Debug, BinaryIO)]
+pub(crate) struct BaselineMetadata {
+    pub(crate) read_head: u64,
+    pub(crate) write_head: u64,
+    pub(crate) buffer_size: u64,
+    pub(crate) current_size: u64,
+}
+
+#[derive(BinaryIO)]
+pub(crate) struct BaselineSample {
+    seq: u32,
+    values: [f32; 10],
+    timestamp: u32,
+}
+
+pub(crate) const BASELINE_METADATA_SIZE: u64 = 32;
+pub(crate) const BASELINE_SAMPLE_SIZE: u64 = 48;
+
+impl From<BaselineSample> for SensorData {
+    fn from(sample: BaselineSample) -> Self {
+        SensorData::new(sample.seq, sample.values, sample.timestamp)
     }
 }
 
+// the metadata of a file in the baseline layout, None if `header` does not look like one. a slot
+// starts with the length and checksum of its frame, which make a read head far past any buffer
+pub(crate) fn baseline_metadata(header: &[u8], len: u64) -> Option<BaselineMetadata> {
+    let (metadata, _) = BaselineMetadata::from_slice_endian(header, Endian::NATIVE).ok()?;
+    let records = len.checked_sub(BASELINE_METADATA_SIZE)?;
+    let size = metadata.buffer_size;
+    let consistent = size > 0
+        && metadata.read_head < size
+        && metadata.write_head < size
+        && metadata.current_size <= size
+        && (metadata.read_head + metadata.current_size) % size == metadata.write_head
+        && records % BASELINE_SAMPLE_SIZE == 0
+        && records / BASELINE_SAMPLE_SIZE <= size;
+    consistent.then_some(metadata)
+}
+
+// reads the metadata of a locked file from the newest valid slot, None if the file is still empty
+pub fn read_metadata(file: &std::fs::File) -> Result<Option<SensorFileMetadata>, SensorDataError> {
+    let mut reader = BufReader::new(file);
+    reader.rewind()?;
+    let mut header = vec![];
+    reader.take(DATA_OFFSET).read_to_end(&mut header)?;
+    let [first, second] = decode_metadata_slots(&header);
+    match (first, second) {
+        (Ok(a), Ok(b)) => Ok(Some(if a.generation > b.generation { a } else { b })),
+        (Ok(m), Err(_)) | (Err(_), Ok(m)) => Ok(Some(m)),
+        // a crash before the first metadata write leaves nothing but a hole
+        _ if header.iter().all(|&b| b == 0) => Ok(None),
+        _ if baseline_metadata(&header, file.metadata()?.len()).is_some() => {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the file has the layout of the first release, upgrade it with sensors-fsck --repair",
+            )
+            .into())
+        }
+        (Err(e), Err(_)) => Err(SensorDataError::MetadataReadError(e)),
+    }
+}
+
+// writes the metadata of a locked file to the slot that does not hold the current generation
+pub fn write_metadata(
+    file: &std::fs::File,
+    metadata: &mut SensorFileMetadata,
+) -> Result<(), SensorDataError> {
+    let generation = metadata.generation + 1;
+    let mut frame = FramedWriter::new(vec![]);
+    frame.write(&SlotPayload {
+        generation,
+        metadata: *metadata,
+    })?;
+    let mut writer = BufWriter::new(file);
+    writer.seek(std::io::SeekFrom::Start(
+        generation % 2 * METADATA_SLOT_SIZE,
+    ))?;
+    writer.write_all(&frame.into_inner())?;
+    writer.flush()?;
+    metadata.generation = generation;
+    Ok(())
+}
+
+pub fn record_offset(index: u64) -> u64 {
+    DATA_OFFSET + index * SENSOR_DATA_SIZE
+}
+
+// the free functions are the file backend of SensorRing, kept for the tools working on the file
+pub fn write_sample(
+    file: &std::fs::File,
+    metadata: &mut SensorFileMetadata,
+    sample: &SensorData,
+) -> Result<bool, SensorDataError> {
+    SensorRing::write_sample(file, metadata, sample)
+}
+
+pub fn read_sample(
+    file: &std::fs::File,
+    metadata: &mut SensorFileMetadata,
+    consumer: &str,
+) -> Result<Option<SensorData>, SensorDataError> {
+    SensorRing::read_sample(file, metadata, consumer)
+}
+
+pub fn peek_sample(
+    file: &std::fs::File,
+    metadata: &SensorFileMetadata,
+    consumer: &str,
+    ahead: u64,
+) -> Result<Option<SensorData>, SensorDataError> {
+    SensorRing::peek_sample(file, metadata, consumer, ahead)
+}
+
 pub fn sensor_lock_file(file: &std::fs::File) -> Result<(), SensorDataError> {
     file.lock_exclusive().map_err(|e| SensorDataError::LockError(e.into()))
 }
@@ -118,14 +538,17 @@ pub fn simulate_sensor(sensor_num: u32) -> SensorData {
     for v in &mut values {
         *v = rng.gen::<f32>();
     }
-    let timestamp = match std::time::SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(n) => n.as_secs() as u32,
-        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
-    };
     SensorData {
         seq: sensor_num,
         values,
-        timestamp,
+        timestamp: unix_time(),
+    }
+}
+
+pub(crate) fn unix_time() -> u32 {
+    match std::time::SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(n) => n.as_secs() as u32,
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
     }
 }
 
@@ -139,4 +562,65 @@ pub struct Args {
     pub samples: u64,
     #[clap(short, long, default_value = "false")]
     pub verbose: bool,
+    /// stored in the file by the producer, the file keeps its current policy when not given
+    #[clap(long, value_enum)]
+    pub on_full: Option<FullPolicy>,
+    /// cursor the consumer reads with, see sensors-consumers
+    #[clap(long, default_value = DEFAULT_CONSUMER)]
+    pub name: String,
+    #[clap(long, value_enum, default_value_t)]
+    pub ring: RingKind,
+    /// serves OpenMetrics at http://<address>/metrics, as 127.0.0.1:9100
+    #[clap(long)]
+    pub metrics: Option<String>,
+}
+
+impl Args {
+    // where the ring lives, a shm ring takes the file name of --file
+    pub fn ring_path(&self) -> PathBuf {
+        let file = Path::new(&self.file);
+        match self.ring {
+            RingKind::File | RingKind::Mapped => file.to_path_buf(),
+            RingKind::Shm => {
+                Path::new(SHM_DIR).join(file.file_name().unwrap_or(file.as_os_str()))
+            }
+        }
+    }
+
+    // the metrics of this process, served when --metrics is given
+    pub fn open_metrics(&self) -> Result<Arc<Metrics>, SensorDataError> {
+        let metrics = Arc::new(Metrics::new());
+        if let Some(address) = &self.metrics {
+            let server = MetricsServer::bind(address, Arc::clone(&metrics))?;
+            println!("Serving metrics on http://{}/metrics", server.local_addr()?);
+            server.spawn();
+        }
+        Ok(metrics)
+    }
+
+    // a ring of --samples records, NotFound if it does not exist and `create` is false
+    pub fn open_ring(
+        &self,
+        create: bool,
+    ) -> Result<Box<dyn SensorRing + Send + Sync>, SensorDataError> {
+        let path = self.ring_path();
+        if !create && !path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} does not exist", path.display()),
+            )
+            .into());
+        }
+        Ok(match self.ring {
+            RingKind::File => Box::new(
+                std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)?,
+            ),
+            RingKind::Mapped | RingKind::Shm => Box::new(MappedRing::open(&path, self.samples)?),
+        })
+    }
 }
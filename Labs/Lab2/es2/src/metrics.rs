@@ -0,0 +1,420 @@
+use crate::{
+    consume, produce, unix_time, FullPolicy, Produced, SensorData, SensorDataError,
+    SensorFileMetadata, SensorRing,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Default)]
+struct State {
+    // the metadata seen last, by a producer or a consumer
+    metadata: Option<SensorFileMetadata>,
+    written: u64,
+    // by the policy that lost them
+    dropped: BTreeMap<&'static str, u64>,
+    // the producer waited for the consumers of a full buffer
+    blocked: u64,
+    consumed: BTreeMap<String, u64>,
+    lock_waits: u64,
+    lock_wait: Duration,
+    // timestamp of the newest sample of every sensor
+    last_sample: BTreeMap<u32, u32>,
+    // the counters as the previous scrape saw them, the rates are over the time since
+    scraped: Option<Scrape>,
+}
+
+struct Scrape {
+    at: Instant,
+    written: u64,
+    consumed: BTreeMap<String, u64>,
+}
+
+impl State {
+    // a process that produces and consumes sees samples again once they are read
+    fn saw(&mut self, sample: &SensorData) {
+        let last = self.last_sample.entry(sample.seq()).or_default();
+        *last = (*last).max(sample.timestamp());
+    }
+}
+
+// what a producer or a consumer did with a ring, for a /metrics endpoint. the rates are samples
+// per second since the previous scrape, or since the metrics were created for the first one
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+// times the lock of the ring it wraps
+struct Metered<'a> {
+    ring: &'a dyn SensorRing,
+    metrics: &'a Metrics,
+}
+
+impl SensorRing for Metered<'_> {
+    fn lock(&self) -> Result<(), SensorDataError> {
+        let start = Instant::now();
+        self.ring.lock()?;
+        let mut state = self.metrics.state();
+        state.lock_waits += 1;
+        state.lock_wait += start.elapsed();
+        Ok(())
+    }
+
+    fn unlock(&self) -> Result<(), SensorDataError> {
+        self.ring.unlock()
+    }
+
+    fn load_metadata(&self) -> Result<Option<SensorFileMetadata>, SensorDataError> {
+        self.ring.load_metadata()
+    }
+
+    fn store_metadata(&self, metadata: &mut SensorFileMetadata) -> Result<(), SensorDataError> {
+        self.ring.store_metadata(metadata)
+    }
+
+    fn load_record(&self, index: u64) -> Result<SensorData, SensorDataError> {
+        self.ring.load_record(index)
+    }
+
+    fn store_record(&self, index: u64, sample: &SensorData) -> Result<(), SensorDataError> {
+        self.ring.store_record(index, sample)
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            state: Mutex::new(State {
+                scraped: Some(Scrape {
+                    at: Instant::now(),
+                    written: 0,
+                    consumed: BTreeMap::new(),
+                }),
+                ..State::default()
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // `produce`, recorded
+    pub fn produce(
+        &self,
+        ring: &dyn SensorRing,
+        sample: &SensorData,
+        size: u64,
+        policy: Option<FullPolicy>,
+    ) -> Result<(Produced, SensorFileMetadata), SensorDataError> {
+        let metered = Metered {
+            ring,
+            metrics: self,
+        };
+        let (produced, metadata) = produce(&metered, sample, size, policy)?;
+        self.produced(produced, sample, &metadata);
+        Ok((produced, metadata))
+    }
+
+    // `consume`, recorded
+    pub fn consume(
+        &self,
+        ring: &dyn SensorRing,
+        consumer: &str,
+        max: usize,
+    ) -> Result<(Vec<SensorData>, Option<SensorFileMetadata>), SensorDataError> {
+        let metered = Metered {
+            ring,
+            metrics: self,
+        };
+        let (samples, metadata) = consume(&metered, consumer, max)?;
+        self.consumed(consumer, &samples, metadata.as_ref());
+        Ok((samples, metadata))
+    }
+
+    pub fn produced(&self, produced: Produced, sample: &SensorData, metadata: &SensorFileMetadata) {
+        let mut state = self.state();
+        state.metadata = Some(*metadata);
+        match produced {
+            Produced::Written => state.written += 1,
+            Produced::Overwritten => {
+                state.written += 1;
+                *state.dropped.entry("overwrite_oldest").or_default() += 1;
+            }
+            Produced::Dropped => *state.dropped.entry("drop_newest").or_default() += 1,
+            Produced::Blocked => state.blocked += 1,
+        }
+        if matches!(produced, Produced::Written | Produced::Overwritten) {
+            state.saw(sample);
+        }
+    }
+
+    // the metadata is not known to a remote consumer
+    pub fn consumed(
+        &self,
+        consumer: &str,
+        samples: &[SensorData],
+        metadata: Option<&SensorFileMetadata>,
+    ) {
+        let mut state = self.state();
+        if let Some(metadata) = metadata {
+            state.metadata = Some(*metadata);
+        }
+        *state.consumed.entry(consumer.to_string()).or_default() += samples.len() as u64;
+        for sample in samples {
+            state.saw(sample);
+        }
+    }
+
+    // the OpenMetrics text exposition, sample ages relative to `now`
+    pub fn render_at(&self, now: u32) -> String {
+        let mut state = self.state();
+        let scrape = Scrape {
+            at: Instant::now(),
+            written: state.written,
+            consumed: state.consumed.clone(),
+        };
+        let previous = state.scraped.replace(scrape);
+        let mut out = String::new();
+        if let Some(metadata) = &state.metadata {
+            family(
+                &mut out,
+                "sensors_buffer_size",
+                "gauge",
+                "Samples the ring can hold.",
+            );
+            writeln!(out, "sensors_buffer_size {}", metadata.buffer_size()).unwrap();
+            family(
+                &mut out,
+                "sensors_current_size",
+                "gauge",
+                "Samples not read yet by the slowest consumer.",
+            );
+            writeln!(out, "sensors_current_size {}", metadata.len()).unwrap();
+            family(
+                &mut out,
+                "sensors_unread_samples",
+                "gauge",
+                "Samples not read yet by each consumer.",
+            );
+            for cursor in metadata.cursors() {
+                let name = escape(&cursor.name());
+                writeln!(
+                    out,
+                    "sensors_unread_samples{{consumer=\"{}\"}} {}",
+                    name,
+                    cursor.len()
+                )
+                .unwrap();
+            }
+        }
+        family(
+            &mut out,
+            "sensors_written_samples",
+            "counter",
+            "Samples written to the ring.",
+        );
+        writeln!(out, "sensors_written_samples_total {}", state.written).unwrap();
+        family(
+            &mut out,
+            "sensors_written_samples_per_second",
+            "gauge",
+            "Samples written per second since the previous scrape.",
+        );
+        let rate = |count: u64, before: Option<u64>| match &previous {
+            Some(previous) => {
+                let elapsed = previous.at.elapsed().as_secs_f64();
+                let count = count.saturating_sub(before.unwrap_or(0)) as f64;
+                if elapsed > 0.0 {
+                    count / elapsed
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+        let written = rate(state.written, previous.as_ref().map(|p| p.written));
+        writeln!(out, "sensors_written_samples_per_second {}", written).unwrap();
+        family(
+            &mut out,
+            "sensors_dropped_samples",
+            "counter",
+            "Samples lost to a full ring, by policy.",
+        );
+        for (policy, count) in &state.dropped {
+            writeln!(
+                out,
+                "sensors_dropped_samples_total{{policy=\"{}\"}} {}",
+                policy, count
+            )
+            .unwrap();
+        }
+        family(
+            &mut out,
+            "sensors_blocked_writes",
+            "counter",
+            "Writes that waited for the consumers of a full ring.",
+        );
+        writeln!(out, "sensors_blocked_writes_total {}", state.blocked).unwrap();
+        family(
+            &mut out,
+            "sensors_consumed_samples",
+            "counter",
+            "Samples read from the ring, by consumer.",
+        );
+        for (consumer, count) in &state.consumed {
+            let name = escape(consumer);
+            writeln!(
+                out,
+                "sensors_consumed_samples_total{{consumer=\"{}\"}} {}",
+                name, count
+            )
+            .unwrap();
+        }
+        family(
+            &mut out,
+            "sensors_consumed_samples_per_second",
+            "gauge",
+            "Samples read per second since the previous scrape, by consumer.",
+        );
+        for (consumer, count) in &state.consumed {
+            let before = previous
+                .as_ref()
+                .and_then(|p| p.consumed.get(consumer).copied());
+            writeln!(
+                out,
+                "sensors_consumed_samples_per_second{{consumer=\"{}\"}} {}",
+                escape(consumer),
+                rate(*count, before)
+            )
+            .unwrap();
+        }
+        family(
+            &mut out,
+            "sensors_lock_wait_seconds",
+            "summary",
+            "Time spent waiting for the ring lock.",
+        );
+        writeln!(out, "sensors_lock_wait_seconds_count {}", state.lock_waits).unwrap();
+        let wait = state.lock_wait.as_secs_f64();
+        writeln!(out, "sensors_lock_wait_seconds_sum {}", wait).unwrap();
+        family(
+            &mut out,
+            "sensors_last_sample_age_seconds",
+            "gauge",
+            "Age of the newest sample of each sensor.",
+        );
+        for (sensor, timestamp) in &state.last_sample {
+            let age = now.saturating_sub(*timestamp);
+            writeln!(
+                out,
+                "sensors_last_sample_age_seconds{{sensor=\"{}\"}} {}",
+                sensor, age
+            )
+            .unwrap();
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+
+    pub fn render(&self) -> String {
+        self.render_at(unix_time())
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# TYPE {} {}\n# HELP {} {}", name, kind, name, help).unwrap();
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// serves the metrics of a producer or consumer at GET /metrics, one request at a time
+pub struct MetricsServer {
+    listener: TcpListener,
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsServer {
+    pub fn bind(address: &str, metrics: Arc<Metrics>) -> Result<Self, SensorDataError> {
+        Ok(MetricsServer {
+            listener: TcpListener::bind(address)?,
+            metrics,
+        })
+    }
+
+    // with the actual port when bound to port 0
+    pub fn local_addr(&self) -> Result<SocketAddr, SensorDataError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    // serves in the background for as long as the process runs
+    pub fn spawn(self) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            if let Err(e) = self.run() {
+                eprintln!("Metrics server stopped: {:?}", e);
+            }
+        })
+    }
+
+    pub fn run(self) -> Result<(), SensorDataError> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            // a scraper that went away is not our problem
+            if let Err(e) = self.respond(stream) {
+                eprintln!("Metrics request failed: {:?}", e);
+            }
+        }
+    }
+
+    fn respond(&self, stream: TcpStream) -> Result<(), SensorDataError> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        // the headers are not needed
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+        let mut words = request.split_whitespace();
+        let (status, content_type, body) = match (words.next(), words.next()) {
+            (Some("GET"), Some("/metrics")) => {
+                ("200 OK", OPENMETRICS_CONTENT_TYPE, self.metrics.render())
+            }
+            (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "only GET is supported\n".to_string(),
+            ),
+        };
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use crate::SensorDataError;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+// wakes a process when another one writes to the sensor file (inotify on linux), so that
+// neither side has to poll it
+pub struct FileNotifier {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+// calls `on_change` on the watcher thread whenever the file changes, for callers that wait for
+// more than the file. the file is watched as long as the returned watcher lives
+pub(crate) fn watch_with(
+    path: &Path,
+    mut on_change: impl FnMut() + Send + 'static,
+) -> Result<RecommendedWatcher, SensorDataError> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if event.map_or(true, |e| {
+            matches!(e.kind, EventKind::Modify(_) | EventKind::Create(_))
+        }) {
+            on_change();
+        }
+    })
+    .map_err(SensorDataError::WatchError)?;
+    watcher
+        .watch(path, RecursiveMode::NonRecursive)
+        .map_err(SensorDataError::WatchError)?;
+    Ok(watcher)
+}
+
+impl FileNotifier {
+    pub fn watch(path: &Path) -> Result<Self, SensorDataError> {
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(SensorDataError::WatchError)?;
+        watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(SensorDataError::WatchError)?;
+        Ok(FileNotifier {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // forgets the changes seen so far. call it before looking at the file: a change made after
+    // that still wakes up the next `wait`, so none can be missed
+    pub fn clear(&self) {
+        while self.events.try_recv().is_ok() {}
+    }
+
+    // blocks until the file changes or `timeout` expires, returns false on timeout.
+    // the change may be our own write, callers must check the file again anyway
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(left) {
+                Ok(Ok(event))
+                    if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) =>
+                {
+                    return true
+                }
+                Ok(Ok(_)) => continue,
+                // the watcher lost track of something, better wake up for nothing
+                Ok(Err(_)) => return true,
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+}
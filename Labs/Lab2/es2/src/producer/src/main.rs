@@ -1,54 +1,62 @@
 use clap::Parser;
 use sensors::{
-    sensor_lock_file, sensor_unlock_file, simulate_sensor, SensorData, SensorFileMetadata, Args, SensorDataError
+    Args, FileNotifier, Produced, SensorData, SensorDataError, SourceArgs, FALLBACK_WAIT,
 };
-use std::{
-    fs::OpenOptions,
-    io::{Seek, Write},
-};
-use binary_io::BinPack;
+
+#[derive(Parser)]
+struct ProducerArgs {
+    #[clap(flatten)]
+    args: Args,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
 
 fn main() -> Result<(), SensorDataError> {
-    let args = Args::parse();
-    let file = OpenOptions::new()
-        .write(true)
-        .read(true)
-        .create(true)
-        .open(&args.file)?;
-    const SENSOR_DATA_SIZE: u64 = std::mem::size_of::<SensorData>() as u64;
-    const METADATA_SIZE: u64 = std::mem::size_of::<SensorFileMetadata>() as u64;
-    let mut sensor_num = 0u32;
+    let ProducerArgs {
+        args,
+        source: source_args,
+    } = ProducerArgs::parse();
+    let mut source = source_args.open(args.sensors)?;
+    let ring = args.open_ring(true)?;
+    let metrics = args.open_metrics()?;
+    // woken up by the consumers when they make room in a full buffer
+    let notifier = FileNotifier::watch(&args.ring_path())?;
+    let mut previous: Option<SensorData> = None;
+    // kept while the buffer is full, so that a replay does not lose it
+    let mut pending = None;
+    let mut produced = 0u64;
     loop {
-        sensor_lock_file(&file)?;
-        let mut writer = std::io::BufWriter::new(&file);
-        let mut reader = std::io::BufReader::new(&file);
-        // write data to file
-        // always read metadata from start of file.
-        reader.rewind()?;
-        let mut metadata = match SensorFileMetadata::from_bytes(&mut reader) {
-            Some(m) => m,
-            None => SensorFileMetadata::from_size(args.samples),
-        };
-        println!("Read metadata {:?}", metadata);
-        writer.seek(std::io::SeekFrom::Start(
-            metadata.write_head * SENSOR_DATA_SIZE + METADATA_SIZE,
-        ))?;
-        let d = simulate_sensor(sensor_num);
-        if args.verbose {
-            println!("Writing data {:?}", d);
+        if pending.is_none() {
+            if source_args.count.is_some_and(|count| produced >= count) {
+                return Ok(());
+            }
+            let Some(d) = source.next_sample()? else {
+                return Ok(());
+            };
+            if let Some(previous) = &previous {
+                std::thread::sleep(source_args.pause(previous, &d));
+            }
+            pending = Some(d);
         }
-        d.to_bytes(&mut writer)?;
-        match metadata.advance_write_head() {
-            Ok(_) => (),
-            Err(e) => {
-                println!("Error: {}", e);
+        notifier.clear();
+        let d = pending.take().unwrap();
+        // a fresh ring has no metadata yet, it is set up for --samples records
+        let (outcome, metadata) = metrics.produce(&*ring, &d, args.samples, args.on_full)?;
+        match outcome {
+            Produced::Blocked => {
+                pending = Some(d);
+                println!("Buffer is full, waiting for the consumers");
+                notifier.wait(FALLBACK_WAIT);
+                continue;
+            }
+            Produced::Dropped => println!("Buffer is full, dropping sample of sensor {}", d.seq()),
+            Produced::Written | Produced::Overwritten if args.verbose => {
+                println!("Writing data {:?}", d)
             }
+            Produced::Written | Produced::Overwritten => {}
         }
-        writer.rewind()?;
-        metadata.to_bytes(&mut writer)?;
-        writer.flush()?;
-        sensor_unlock_file(&file)?;
-        sensor_num = (sensor_num + 1) % args.sensors;
-        std::thread::sleep(std::time::Duration::from_millis(1000));
+        println!("After writing: {:?}", metadata);
+        previous = Some(d);
+        produced += 1;
     }
 }
@@ -0,0 +1,223 @@
+use crate::{SensorData, SensorDataError};
+use binary_io::{BinaryIO, BinaryIoError, FramedReader, FramedWriter};
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// the server sends at most this many samples a client has not acknowledged yet
+pub const MAX_IN_FLIGHT: u64 = 64;
+
+// every message is a frame of binary_io::FramedWriter. the client subscribes with the name of a
+// registered consumer, then the server streams the unread samples of that cursor without moving
+// it: the cursor only moves when the client acknowledges samples, so whatever was not
+// acknowledged is sent again on the next subscription
+#[derive(Debug, Clone, PartialEq, BinaryIO)]
+#[binary_io(endian = "little", tag = "u8")]
+pub enum Request {
+    Subscribe {
+        #[binary_io(len = "u8")]
+        name: String,
+    },
+    // drops the unread samples older than `timestamp`, streaming starts again after them
+    Seek {
+        timestamp: u32,
+    },
+    // the oldest `count` samples sent are done with
+    Ack {
+        count: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, BinaryIO)]
+#[binary_io(endian = "little", tag = "u8")]
+pub enum Response {
+    Subscribed {
+        unread: u64,
+    },
+    Sample(SensorData),
+    Sought {
+        skipped: u64,
+    },
+    Error {
+        #[binary_io(len = "u16")]
+        message: String,
+    },
+}
+
+// `unix:<path>` or a tcp address as `127.0.0.1:7878`, unix sockets only exist on unix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.strip_prefix("unix:") {
+            Some("") => Err("missing socket path".to_string()),
+            #[cfg(unix)]
+            Some(path) => Ok(Endpoint::Unix(path.into())),
+            #[cfg(not(unix))]
+            Some(_) => Err("unix sockets are not supported on this platform".to_string()),
+            None => Ok(Endpoint::Tcp(
+                text.strip_prefix("tcp:").unwrap_or(text).to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(endpoint: &Endpoint) -> Result<Self, std::io::Error> {
+        Ok(match endpoint {
+            Endpoint::Tcp(address) => Stream::Tcp(TcpStream::connect(address)?),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Stream::Unix(UnixStream::connect(path)?),
+        })
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self, std::io::Error> {
+        Ok(match self {
+            Stream::Tcp(s) => Stream::Tcp(s.try_clone()?),
+            #[cfg(unix)]
+            Stream::Unix(s) => Stream::Unix(s.try_clone()?),
+        })
+    }
+
+    pub(crate) fn shutdown(&self) {
+        // the other end may be gone already
+        let _ = match self {
+            Stream::Tcp(s) => s.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+// a consumer on the other side of a sensors-server
+pub struct Client {
+    reader: FramedReader<Stream>,
+    writer: FramedWriter<Stream>,
+}
+
+impl Client {
+    pub fn connect(endpoint: &Endpoint) -> Result<Self, SensorDataError> {
+        let stream = Stream::connect(endpoint)?;
+        Ok(Client {
+            reader: FramedReader::new(stream.try_clone()?),
+            writer: FramedWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, request: &Request) -> Result<(), SensorDataError> {
+        self.writer.write(request)?;
+        Ok(())
+    }
+
+    // None once the server closed the connection
+    fn receive(&mut self) -> Result<Option<Response>, SensorDataError> {
+        match self.reader.read() {
+            Ok(Response::Error { message }) => Err(SensorDataError::RemoteError(message)),
+            Ok(response) => Ok(Some(response)),
+            Err(BinaryIoError::Eof) => Ok(None),
+            Err(e) => Err(SensorDataError::ProtocolError(e)),
+        }
+    }
+
+    fn closed() -> SensorDataError {
+        SensorDataError::IoError(std::io::ErrorKind::UnexpectedEof.into())
+    }
+
+    // returns the number of samples the consumer has not read yet
+    pub fn subscribe(&mut self, name: &str) -> Result<u64, SensorDataError> {
+        self.send(&Request::Subscribe {
+            name: name.to_string(),
+        })?;
+        loop {
+            match self.receive()?.ok_or_else(Self::closed)? {
+                Response::Subscribed { unread } => return Ok(unread),
+                // from a previous subscription
+                _ => continue,
+            }
+        }
+    }
+
+    // returns the number of samples dropped. samples received but not acknowledged yet are sent
+    // again if they are not older than `timestamp`
+    pub fn seek(&mut self, timestamp: u32) -> Result<u64, SensorDataError> {
+        self.send(&Request::Seek { timestamp })?;
+        loop {
+            match self.receive()?.ok_or_else(Self::closed)? {
+                Response::Sought { skipped } => return Ok(skipped),
+                // sent before the server got the request
+                _ => continue,
+            }
+        }
+    }
+
+    // blocks until the server has a sample, None once it closed the connection
+    pub fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError> {
+        loop {
+            match self.receive()? {
+                Some(Response::Sample(sample)) => return Ok(Some(sample)),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    pub fn ack(&mut self, count: u32) -> Result<(), SensorDataError> {
+        self.send(&Request::Ack { count })
+    }
+}
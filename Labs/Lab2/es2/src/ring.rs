@@ -0,0 +1,529 @@
+use crate::{
+    not_registered, read_metadata, record_offset, sensor_lock_file, sensor_unlock_file,
+    write_metadata, FullPolicy, Metrics, SensorData, SensorDataError, SensorFileMetadata,
+    DATA_OFFSET, SENSOR_DATA_SIZE,
+};
+use binary_io::{BinPack, BinaryIoError, MappedRecords};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+pub const SHM_DIR: &str = "/dev/shm";
+
+// how the producer and consumer processes share the ring
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RingKind {
+    // records read and written through the file
+    #[default]
+    File,
+    // the same file with the records memory mapped
+    Mapped,
+    // a mapped file of the same name in /dev/shm
+    Shm,
+}
+
+// where a ring of samples lives: the metadata and the records are only accessed between `lock`
+// and `unlock`, which exclude every other user of the same ring, thread or process. file backed
+// rings are locked per open file, as flock: each thread or process opens its own
+pub trait SensorRing {
+    fn lock(&self) -> Result<(), SensorDataError>;
+    fn unlock(&self) -> Result<(), SensorDataError>;
+    // None while nothing was stored yet
+    fn load_metadata(&self) -> Result<Option<SensorFileMetadata>, SensorDataError>;
+    fn store_metadata(&self, metadata: &mut SensorFileMetadata) -> Result<(), SensorDataError>;
+    fn load_record(&self, index: u64) -> Result<SensorData, SensorDataError>;
+    fn store_record(&self, index: u64, sample: &SensorData) -> Result<(), SensorDataError>;
+
+    // stores a sample at the write head. false if the buffer is full and the policy does not
+    // allow overwriting, the caller decides whether to drop the sample or wait
+    fn write_sample(
+        &self,
+        metadata: &mut SensorFileMetadata,
+        sample: &SensorData,
+    ) -> Result<bool, SensorDataError> {
+        if metadata.is_full() && metadata.policy != FullPolicy::OverwriteOldest {
+            return Ok(false);
+        }
+        self.store_record(metadata.write_head, sample)?;
+        metadata.advance_write_head()?;
+        Ok(true)
+    }
+
+    // the unread sample `ahead` positions after the read head of the consumer, without moving it
+    fn peek_sample(
+        &self,
+        metadata: &SensorFileMetadata,
+        consumer: &str,
+        ahead: u64,
+    ) -> Result<Option<SensorData>, SensorDataError> {
+        let cursor = match metadata.cursor(consumer) {
+            Some(c) if c.len() <= ahead => return Ok(None),
+            Some(c) => *c,
+            None => return Err(not_registered(consumer).into()),
+        };
+        let index = (cursor.read_head + ahead) % metadata.buffer_size();
+        self.load_record(index).map(Some)
+    }
+
+    // the next sample for `consumer`, None once it has read everything
+    fn read_sample(
+        &self,
+        metadata: &mut SensorFileMetadata,
+        consumer: &str,
+    ) -> Result<Option<SensorData>, SensorDataError> {
+        let sample = self.peek_sample(metadata, consumer, 0)?;
+        if sample.is_some() {
+            metadata.advance_read_head(consumer)?;
+        }
+        Ok(sample)
+    }
+}
+
+// the ring file as the producer and consumer processes share it
+impl SensorRing for File {
+    fn lock(&self) -> Result<(), SensorDataError> {
+        sensor_lock_file(self)
+    }
+
+    fn unlock(&self) -> Result<(), SensorDataError> {
+        sensor_unlock_file(self)
+    }
+
+    fn load_metadata(&self) -> Result<Option<SensorFileMetadata>, SensorDataError> {
+        read_metadata(self)
+    }
+
+    fn store_metadata(&self, metadata: &mut SensorFileMetadata) -> Result<(), SensorDataError> {
+        write_metadata(self, metadata)
+    }
+
+    fn load_record(&self, index: u64) -> Result<SensorData, SensorDataError> {
+        let mut reader = BufReader::new(self);
+        reader.seek(SeekFrom::Start(record_offset(index)))?;
+        SensorData::from_bytes(&mut reader).map_err(SensorDataError::DataReadError)
+    }
+
+    fn store_record(&self, index: u64, sample: &SensorData) -> Result<(), SensorDataError> {
+        let mut writer = BufWriter::new(self);
+        writer.seek(SeekFrom::Start(record_offset(index)))?;
+        sample.to_bytes(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// the same layout as the ring file with the records memory mapped, so that it can share a file
+// with processes using the file directly. in /dev/shm the ring never reaches a disk
+pub struct MappedRing {
+    file: File,
+    records: Mutex<MappedRecords<SensorData>>,
+}
+
+impl MappedRing {
+    // opens the ring file, or creates it for `size` samples. records that were not written yet
+    // are filled in, as a mapping cannot grow with the file
+    pub fn open(path: &Path, size: u64) -> Result<Self, SensorDataError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        with_lock(&file, || Self::fill(&file, size))?;
+        let records =
+            MappedRecords::open(&file, DATA_OFFSET).map_err(SensorDataError::DataReadError)?;
+        Ok(MappedRing {
+            file,
+            records: Mutex::new(records),
+        })
+    }
+
+    // a ring in /dev/shm, shared by the processes that open the same name
+    pub fn shm(name: &str, size: u64) -> Result<Self, SensorDataError> {
+        Self::open(&Path::new(SHM_DIR).join(name), size)
+    }
+
+    fn fill(file: &File, size: u64) -> Result<(), SensorDataError> {
+        let mut metadata = match file.load_metadata()? {
+            Some(metadata) => metadata,
+            None => {
+                let mut metadata = SensorFileMetadata::from_size(size);
+                file.store_metadata(&mut metadata)?;
+                metadata
+            }
+        };
+        let written = file.metadata()?.len().saturating_sub(DATA_OFFSET) / SENSOR_DATA_SIZE;
+        // a record torn by a crash is rewritten as well
+        file.set_len(record_offset(written.min(metadata.buffer_size())))?;
+        for index in written..metadata.buffer_size() {
+            file.store_record(index, &SensorData::default())?;
+        }
+        // keeps both metadata slots valid for the checks of sensors-fsck
+        if metadata.generation() == 1 {
+            file.store_metadata(&mut metadata)?;
+        }
+        Ok(())
+    }
+
+    fn records(&self) -> MutexGuard<'_, MappedRecords<SensorData>> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl SensorRing for MappedRing {
+    fn lock(&self) -> Result<(), SensorDataError> {
+        sensor_lock_file(&self.file)
+    }
+
+    fn unlock(&self) -> Result<(), SensorDataError> {
+        sensor_unlock_file(&self.file)
+    }
+
+    fn load_metadata(&self) -> Result<Option<SensorFileMetadata>, SensorDataError> {
+        self.file.load_metadata()
+    }
+
+    fn store_metadata(&self, metadata: &mut SensorFileMetadata) -> Result<(), SensorDataError> {
+        self.file.store_metadata(metadata)
+    }
+
+    fn load_record(&self, index: u64) -> Result<SensorData, SensorDataError> {
+        self.records()
+            .get(index as usize)
+            .map_err(SensorDataError::DataReadError)
+    }
+
+    fn store_record(&self, index: u64, sample: &SensorData) -> Result<(), SensorDataError> {
+        self.records()
+            .set(index as usize, sample)
+            .map_err(SensorDataError::DataReadError)
+    }
+}
+
+#[derive(Default)]
+struct MemoryState {
+    metadata: Option<SensorFileMetadata>,
+    records: Vec<SensorData>,
+    shut_down: bool,
+}
+
+// a ring shared by the threads of a process. besides the SensorRing steps it is a bounded blocking
+// queue: `push` waits for room and `pop` for samples, on condvars signalled as the metadata changes.
+// `push` only waits under the Block policy, the default of this ring
+#[derive(Default)]
+pub struct MemoryRing {
+    locked: Mutex<bool>,
+    unlocked: Condvar,
+    state: Mutex<MemoryState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    // where the queue operations are recorded
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl MemoryRing {
+    pub fn new(size: u64) -> Result<Self, SensorDataError> {
+        if size == 0 {
+            return Err(SensorDataError::ConfigError(
+                "a ring needs room for at least one sample".to_string(),
+            ));
+        }
+        let mut metadata = SensorFileMetadata::from_size(size);
+        metadata.policy = FullPolicy::Block;
+        Ok(MemoryRing {
+            state: Mutex::new(MemoryState {
+                metadata: Some(metadata),
+                records: vec![SensorData::default(); size as usize],
+                shut_down: false,
+            }),
+            ..MemoryRing::default()
+        })
+    }
+
+    // what `push` does when the ring is full
+    pub fn with_policy(self, policy: FullPolicy) -> Self {
+        if let Some(metadata) = &mut self.state().metadata {
+            metadata.policy = policy;
+        }
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // wakes up every thread waiting in `push` or `pop`. nothing can be pushed afterwards, the
+    // samples left can still be popped
+    pub fn shutdown(&self) {
+        self.state().shut_down = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.state().shut_down
+    }
+
+    // waits on `condvar` while `waiting` holds, false once `deadline` passed
+    fn wait(
+        &self,
+        condvar: &Condvar,
+        deadline: Option<Instant>,
+        waiting: impl Fn(&MemoryState) -> bool,
+    ) -> bool {
+        let mut state = self.state();
+        while waiting(&state) && !state.shut_down {
+            state = match deadline {
+                None => condvar.wait(state).unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                        return false;
+                    };
+                    condvar
+                        .wait_timeout(state, left)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        }
+        true
+    }
+
+    fn push_until(
+        &self,
+        sample: &SensorData,
+        deadline: Option<Instant>,
+    ) -> Result<bool, SensorDataError> {
+        let size = self.state().records.len() as u64;
+        let mut blocked = false;
+        loop {
+            if self.is_shut_down() {
+                return Err(SensorDataError::ShutDown);
+            }
+            let (produced, _) = match &self.metrics {
+                Some(metrics) if !blocked => metrics.produce(self, sample, size, None)?,
+                // the push was counted as blocked once already
+                Some(metrics) => {
+                    let (produced, metadata) = produce(self, sample, size, None)?;
+                    if produced != Produced::Blocked {
+                        metrics.produced(produced, sample, &metadata);
+                    }
+                    (produced, metadata)
+                }
+                None => produce(self, sample, size, None)?,
+            };
+            match produced {
+                Produced::Written | Produced::Overwritten => return Ok(true),
+                Produced::Dropped => return Ok(false),
+                Produced::Blocked => blocked = true,
+            }
+            let full = |state: &MemoryState| state.metadata.is_some_and(|m| m.is_full());
+            if !self.wait(&self.not_full, deadline, full) {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn pop_until(
+        &self,
+        consumer: &str,
+        max: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<SensorData>, SensorDataError> {
+        loop {
+            let (samples, _) = match &self.metrics {
+                Some(metrics) => metrics.consume(self, consumer, max)?,
+                None => consume(self, consumer, max)?,
+            };
+            if !samples.is_empty() || self.is_shut_down() {
+                return Ok(samples);
+            }
+            let empty = |state: &MemoryState| {
+                state
+                    .metadata
+                    .and_then(|m| m.cursor(consumer).map(|c| c.is_empty()))
+                    .unwrap_or(true)
+            };
+            if !self.wait(&self.not_empty, deadline, empty) {
+                return Ok(samples);
+            }
+        }
+    }
+
+    // waits for room, an error once shut down. the sample is lost if the policy drops it
+    pub fn push(&self, sample: &SensorData) -> Result<(), SensorDataError> {
+        self.push_until(sample, None).map(|_| ())
+    }
+
+    // false if there was no room in time, or if the policy dropped the sample
+    pub fn push_timeout(
+        &self,
+        sample: &SensorData,
+        timeout: Duration,
+    ) -> Result<bool, SensorDataError> {
+        self.push_until(sample, Instant::now().checked_add(timeout))
+    }
+
+    // waits for the next sample of `consumer`, None once shut down and read up
+    pub fn pop(&self, consumer: &str) -> Result<Option<SensorData>, SensorDataError> {
+        Ok(self.pop_until(consumer, 1, None)?.pop())
+    }
+
+    // None as well if no sample came in time
+    pub fn pop_timeout(
+        &self,
+        consumer: &str,
+        timeout: Duration,
+    ) -> Result<Option<SensorData>, SensorDataError> {
+        let deadline = Instant::now().checked_add(timeout);
+        Ok(self.pop_until(consumer, 1, deadline)?.pop())
+    }
+
+    // waits for at least one sample and takes up to `max`, empty once shut down and read up
+    pub fn pop_up_to(
+        &self,
+        consumer: &str,
+        max: usize,
+    ) -> Result<Vec<SensorData>, SensorDataError> {
+        self.pop_until(consumer, max.max(1), None)
+    }
+}
+
+impl SensorRing for MemoryRing {
+    fn lock(&self) -> Result<(), SensorDataError> {
+        let mut locked = self.locked.lock().unwrap_or_else(PoisonError::into_inner);
+        while *locked {
+            locked = self
+                .unlocked
+                .wait(locked)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *locked = true;
+        Ok(())
+    }
+
+    fn unlock(&self) -> Result<(), SensorDataError> {
+        *self.locked.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        self.unlocked.notify_one();
+        Ok(())
+    }
+
+    fn load_metadata(&self) -> Result<Option<SensorFileMetadata>, SensorDataError> {
+        Ok(self.state().metadata)
+    }
+
+    fn store_metadata(&self, metadata: &mut SensorFileMetadata) -> Result<(), SensorDataError> {
+        metadata.generation += 1;
+        self.state().metadata = Some(*metadata);
+        if !metadata.is_empty() {
+            self.not_empty.notify_all();
+        }
+        if !metadata.is_full() {
+            self.not_full.notify_all();
+        }
+        Ok(())
+    }
+
+    fn load_record(&self, index: u64) -> Result<SensorData, SensorDataError> {
+        let state = self.state();
+        state.records.get(index as usize).copied().ok_or_else(|| {
+            SensorDataError::DataReadError(BinaryIoError::OutOfBounds {
+                index: index as usize,
+                len: state.records.len(),
+            })
+        })
+    }
+
+    fn store_record(&self, index: u64, sample: &SensorData) -> Result<(), SensorDataError> {
+        let mut state = self.state();
+        let index = index as usize;
+        if index >= state.records.len() {
+            state.records.resize(index + 1, SensorData::default());
+        }
+        state.records[index] = *sample;
+        Ok(())
+    }
+}
+
+// runs `f` with the ring locked, and unlocks it whatever `f` returns
+pub fn with_lock<R: SensorRing + ?Sized, T>(
+    ring: &R,
+    f: impl FnOnce() -> Result<T, SensorDataError>,
+) -> Result<T, SensorDataError> {
+    ring.lock()?;
+    let result = f();
+    ring.unlock()?;
+    result
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Produced {
+    Written,
+    // written over the oldest unread sample, which is lost
+    Overwritten,
+    // the buffer was full, the sample is lost
+    Dropped,
+    // the buffer is full and the policy is to wait for the consumers, nothing was written
+    Blocked,
+}
+
+// one step of a producer. a ring without metadata is set up for `size` samples, `policy` replaces
+// the stored one when given. returns the metadata after the step
+pub fn produce<R: SensorRing + ?Sized>(
+    ring: &R,
+    sample: &SensorData,
+    size: u64,
+    policy: Option<FullPolicy>,
+) -> Result<(Produced, SensorFileMetadata), SensorDataError> {
+    with_lock(ring, || {
+        let mut metadata = ring
+            .load_metadata()?
+            .unwrap_or_else(|| SensorFileMetadata::from_size(size));
+        if let Some(policy) = policy {
+            metadata.policy = policy;
+        }
+        if metadata.is_full() && metadata.policy == FullPolicy::Block {
+            return Ok((Produced::Blocked, metadata));
+        }
+        let overwritten = metadata.is_full();
+        let produced = match ring.write_sample(&mut metadata, sample)? {
+            true if overwritten => Produced::Overwritten,
+            true => Produced::Written,
+            false => Produced::Dropped,
+        };
+        ring.store_metadata(&mut metadata)?;
+        Ok((produced, metadata))
+    })
+}
+
+// one step of a consumer: up to `max` of its unread samples. when there are none nothing is stored,
+// so that a consumer watching the ring is not woken up by itself
+pub fn consume<R: SensorRing + ?Sized>(
+    ring: &R,
+    consumer: &str,
+    max: usize,
+) -> Result<(Vec<SensorData>, Option<SensorFileMetadata>), SensorDataError> {
+    with_lock(ring, || {
+        let Some(mut metadata) = ring.load_metadata()? else {
+            return Ok((vec![], None));
+        };
+        let mut samples = vec![];
+        while samples.len() < max {
+            match ring.read_sample(&mut metadata, consumer)? {
+                Some(sample) => samples.push(sample),
+                None => break,
+            }
+        }
+        if !samples.is_empty() {
+            ring.store_metadata(&mut metadata)?;
+        }
+        Ok((samples, Some(metadata)))
+    })
+}
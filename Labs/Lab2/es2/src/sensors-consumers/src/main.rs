@@ -0,0 +1,74 @@
+use clap::{Parser, Subcommand};
+use sensors::{
+    read_metadata, sensor_lock_file, sensor_unlock_file, write_metadata, SensorDataError,
+    SensorFileMetadata,
+};
+use std::fs::OpenOptions;
+
+#[derive(Parser)]
+struct ConsumersArgs {
+    #[clap(short, long, default_value = "sensor_data.bin")]
+    file: String,
+    /// buffer size of a file created by `register`
+    #[clap(long, default_value = "100")]
+    samples: u64,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// adds a cursor that starts at the current write head
+    Register { name: String },
+    /// removes a cursor, the producer no longer waits for it
+    Unregister { name: String },
+    List,
+}
+
+fn run(args: &ConsumersArgs) -> Result<(), SensorDataError> {
+    let file = OpenOptions::new()
+        .write(true)
+        .read(true)
+        .create(matches!(args.command, Command::Register { .. }))
+        .truncate(false)
+        .open(&args.file)?;
+    sensor_lock_file(&file)?;
+    let result = update(&file, args);
+    sensor_unlock_file(&file)?;
+    result
+}
+
+fn update(file: &std::fs::File, args: &ConsumersArgs) -> Result<(), SensorDataError> {
+    let mut metadata = match read_metadata(file)? {
+        Some(m) => m,
+        None => SensorFileMetadata::from_size(args.samples),
+    };
+    match &args.command {
+        Command::Register { name } => metadata.register(name)?,
+        Command::Unregister { name } => metadata.unregister(name)?,
+        Command::List => {
+            println!("{:<16} {:>10} {:>10}", "name", "read head", "unread");
+            for cursor in metadata.cursors() {
+                println!(
+                    "{:<16} {:>10} {:>10}",
+                    cursor.name(),
+                    cursor.read_head,
+                    cursor.len()
+                );
+            }
+            return Ok(());
+        }
+    }
+    write_metadata(file, &mut metadata)
+}
+
+fn main() {
+    let args = ConsumersArgs::parse();
+    if let Err(e) = run(&args) {
+        match e {
+            SensorDataError::IoError(e) => eprintln!("Error: {}", e),
+            e => eprintln!("Error: {:?}", e),
+        }
+        std::process::exit(1);
+    }
+}
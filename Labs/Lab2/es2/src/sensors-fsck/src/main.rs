@@ -0,0 +1,81 @@
+use clap::Parser;
+use sensors::{fsck, sensor_lock_file, sensor_unlock_file, upgrade_baseline, FsckOptions};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process::exit;
+
+/// exits with 0 if the file is fine, 1 if everything that was wrong got repaired and 2 if
+/// problems are left
+#[derive(Parser)]
+struct FsckArgs {
+    #[clap(default_value = "sensor_data.bin")]
+    file: String,
+    /// fix what can be fixed, otherwise the file is only checked
+    #[clap(long)]
+    repair: bool,
+    /// buffer size to rebuild unreadable metadata with, guessed from the file size by default
+    #[clap(long)]
+    samples: Option<u64>,
+}
+
+fn main() {
+    let args = FsckArgs::parse();
+    // a file of the first release is replaced by an upgraded copy, which is then checked
+    let upgraded = args.repair
+        && match upgrade_baseline(Path::new(&args.file)) {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                eprintln!("Error: {}: {:?}", args.file, e);
+                exit(2);
+            }
+        };
+    if upgraded {
+        println!("repaired: upgraded from the layout of the first release");
+    }
+    let file = match OpenOptions::new()
+        .read(true)
+        .write(args.repair)
+        .open(&args.file)
+    {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error: {}: {}", args.file, e);
+            exit(2);
+        }
+    };
+    let options = FsckOptions {
+        repair: args.repair,
+        samples: args.samples,
+    };
+    // the producer and the consumers wait while the file is checked
+    let report = sensor_lock_file(&file).and_then(|_| {
+        let report = fsck(&file, &options);
+        sensor_unlock_file(&file)?;
+        report
+    });
+    let report = match report {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            exit(2);
+        }
+    };
+    for finding in &report.findings {
+        let state = if finding.repaired {
+            "repaired"
+        } else {
+            "found"
+        };
+        println!("{}: {}", state, finding.message);
+    }
+    if report.is_clean() && !upgraded {
+        println!("{}: clean", args.file);
+    } else if report.all_repaired() {
+        exit(1);
+    } else {
+        if !args.repair {
+            println!("run again with --repair to fix");
+        }
+        exit(2);
+    }
+}
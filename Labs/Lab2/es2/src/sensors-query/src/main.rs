@@ -0,0 +1,48 @@
+use clap::Parser;
+use sensors::{read_segment, segments_between, write_csv_line, SensorDataError, CSV_HEADER};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+// prints the archived samples as csv, which the producer can replay with --source csv
+#[derive(Parser)]
+struct QueryArgs {
+    /// the directory the consumer archives to
+    #[clap(short, long, default_value = "archive")]
+    archive: PathBuf,
+    #[clap(long)]
+    sensor: Option<u32>,
+    /// first timestamp included
+    #[clap(long)]
+    since: Option<u32>,
+    /// first timestamp excluded
+    #[clap(long)]
+    until: Option<u32>,
+}
+
+fn main() -> Result<(), SensorDataError> {
+    let args = QueryArgs::parse();
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    writeln!(out, "{}", CSV_HEADER)?;
+    // the segments out of the range are not even opened
+    for path in segments_between(&args.archive, args.since, args.until)? {
+        let mut samples = vec![];
+        let result = read_segment(&path, |sample| {
+            let excluded = args.sensor.is_some_and(|seq| sample.seq() != seq)
+                || args.since.is_some_and(|t| sample.timestamp() < t)
+                || args.until.is_some_and(|t| sample.timestamp() >= t);
+            if !excluded {
+                samples.push(sample);
+            }
+            Ok(())
+        });
+        for sample in &samples {
+            write_csv_line(&mut out, sample)?;
+        }
+        // as a parquet segment still being written, the others are still worth reading
+        if let Err(e) = result {
+            eprintln!("Skipping the rest of {}: {:?}", path.display(), e);
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use clap::Parser;
+use sensors::{Endpoint, SensorDataError, Server};
+
+#[derive(Parser)]
+struct ServerArgs {
+    #[clap(short, long, default_value = "sensor_data.bin")]
+    file: String,
+    /// `unix:<path>` or a tcp address
+    #[clap(long, default_value = "127.0.0.1:7878")]
+    listen: Endpoint,
+}
+
+fn main() -> Result<(), SensorDataError> {
+    let args = ServerArgs::parse();
+    let server = Server::bind(&args.listen, &args.file)?;
+    println!("Serving {} on {}", args.file, server.endpoint()?);
+    server.run()
+}
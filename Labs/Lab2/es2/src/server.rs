@@ -0,0 +1,320 @@
+use crate::notification::watch_with;
+use crate::remote::{Request, Response, Stream, MAX_IN_FLIGHT};
+use crate::{
+    peek_sample, read_metadata, sensor_lock_file, sensor_unlock_file, write_metadata, Endpoint,
+    SensorData, SensorDataError, SensorFileMetadata, FALLBACK_WAIT,
+};
+use binary_io::{BinaryIoError, FramedReader, FramedWriter};
+use std::fs::{File, OpenOptions};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+// serves the samples of a sensor file to remote consumers, a thread per connection
+pub struct Server {
+    listener: Listener,
+    path: PathBuf,
+}
+
+impl Server {
+    pub fn bind(endpoint: &Endpoint, path: impl Into<PathBuf>) -> Result<Self, SensorDataError> {
+        let listener = match endpoint {
+            Endpoint::Tcp(address) => Listener::Tcp(TcpListener::bind(address)?),
+            #[cfg(unix)]
+            Endpoint::Unix(socket) => {
+                // a socket left behind by a previous server, anything else is not ours to remove
+                match std::fs::symlink_metadata(socket) {
+                    Ok(m) if m.file_type().is_socket() => std::fs::remove_file(socket)?,
+                    Ok(_) => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::AlreadyExists,
+                            format!("{} exists and is not a socket", socket.display()),
+                        )
+                        .into())
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+                Listener::Unix(UnixListener::bind(socket)?)
+            }
+        };
+        Ok(Server {
+            listener,
+            path: path.into(),
+        })
+    }
+
+    // where clients connect, with the actual port when bound to port 0
+    pub fn endpoint(&self) -> Result<Endpoint, SensorDataError> {
+        Ok(match &self.listener {
+            Listener::Tcp(l) => Endpoint::Tcp(l.local_addr()?.to_string()),
+            #[cfg(unix)]
+            Listener::Unix(l) => Endpoint::Unix(
+                l.local_addr()?
+                    .as_pathname()
+                    .map(PathBuf::from)
+                    .unwrap_or_default(),
+            ),
+        })
+    }
+
+    pub fn run(self) -> Result<(), SensorDataError> {
+        loop {
+            let stream = match &self.listener {
+                Listener::Tcp(l) => Stream::Tcp(l.accept()?.0),
+                #[cfg(unix)]
+                Listener::Unix(l) => Stream::Unix(l.accept()?.0),
+            };
+            let path = self.path.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = Connection::open(path, stream).and_then(Connection::run) {
+                    eprintln!("Connection closed: {:?}", e);
+                }
+            });
+        }
+    }
+}
+
+enum Wakeup {
+    Request(Request),
+    FileChanged,
+    // the client hung up or sent something that is not a request
+    Closed(Option<BinaryIoError>),
+}
+
+struct Connection {
+    path: PathBuf,
+    file: File,
+    writer: FramedWriter<Stream>,
+    consumer: Option<String>,
+    in_flight: InFlight,
+}
+
+// samples sent and not acknowledged yet
+#[derive(Debug, Default, Copy, Clone)]
+struct InFlight {
+    // position of the oldest one, the read head of the consumer when the window was last looked at
+    start: u64,
+    count: u64,
+    // sent, then overwritten by the producer: the client acknowledges them without moving the
+    // cursor, which has moved past them already
+    overwritten: u64,
+}
+
+impl InFlight {
+    // catches up with the read head, which only moves past samples in flight when the producer
+    // overwrites them. the distance is modulo the buffer size, a producer lapping the whole buffer
+    // between two looks is not noticed
+    fn follow(&mut self, metadata: &SensorFileMetadata, name: &str) {
+        let Some(cursor) = metadata.cursor(name) else {
+            return;
+        };
+        let buffer_size = metadata.buffer_size();
+        if self.count > 0 && buffer_size > 0 {
+            let moved = (cursor.read_head + buffer_size - self.start) % buffer_size;
+            let lost = moved.min(self.count);
+            self.count -= lost;
+            self.overwritten += lost;
+        }
+        self.start = cursor.read_head;
+    }
+}
+
+impl Connection {
+    fn open(path: PathBuf, stream: Stream) -> Result<Self, SensorDataError> {
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        Ok(Connection {
+            path,
+            file,
+            writer: FramedWriter::new(stream),
+            consumer: None,
+            in_flight: InFlight::default(),
+        })
+    }
+
+    fn run(mut self) -> Result<(), SensorDataError> {
+        let (wakeups, wakeup) = channel();
+        let file_changed = wakeups.clone();
+        let _watcher = watch_with(&self.path, move || {
+            let _ = file_changed.send(Wakeup::FileChanged);
+        })?;
+        let stream = self.writer.get_ref().try_clone()?;
+        std::thread::spawn(move || read_requests(stream, wakeups));
+
+        let result = loop {
+            match wakeup.recv_timeout(FALLBACK_WAIT) {
+                Ok(Wakeup::Request(request)) => {
+                    if let Err(e) = self.handle(request) {
+                        break Err(e);
+                    }
+                }
+                Ok(Wakeup::Closed(None)) => break Ok(()),
+                Ok(Wakeup::Closed(Some(e))) => break Err(SensorDataError::ProtocolError(e)),
+                Ok(Wakeup::FileChanged) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break Ok(()),
+            }
+            if let Err(e) = self.send_unread() {
+                break Err(e);
+            }
+        };
+        // stops the request reader as well
+        self.writer.get_ref().shutdown();
+        result
+    }
+
+    // runs `f` on the metadata with the file locked, the metadata is written back if it returns true
+    fn with_metadata<T>(
+        &self,
+        f: impl FnOnce(&mut SensorFileMetadata) -> Result<(T, bool), SensorDataError>,
+    ) -> Result<T, SensorDataError> {
+        sensor_lock_file(&self.file)?;
+        let result = read_metadata(&self.file).and_then(|metadata| {
+            let mut metadata = metadata.unwrap_or_else(|| SensorFileMetadata::from_size(0));
+            let (value, changed) = f(&mut metadata)?;
+            if changed {
+                write_metadata(&self.file, &mut metadata)?;
+            }
+            Ok(value)
+        });
+        sensor_unlock_file(&self.file)?;
+        result
+    }
+
+    fn send(&mut self, response: &Response) -> Result<(), SensorDataError> {
+        self.writer.write(response)?;
+        Ok(())
+    }
+
+    // errors of the client are reported to it, the connection stays open
+    fn handle(&mut self, request: Request) -> Result<(), SensorDataError> {
+        let response = match self.respond(request) {
+            Ok(None) => return Ok(()),
+            Ok(Some(response)) => response,
+            Err(e) => Response::Error {
+                message: match e {
+                    SensorDataError::IoError(e) => e.to_string(),
+                    e => format!("{:?}", e),
+                },
+            },
+        };
+        self.send(&response)
+    }
+
+    fn subscribed(&self) -> Result<String, SensorDataError> {
+        self.consumer.clone().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "not subscribed").into()
+        })
+    }
+
+    fn respond(&mut self, request: Request) -> Result<Option<Response>, SensorDataError> {
+        match request {
+            Request::Subscribe { name } => {
+                let unread = self.with_metadata(|metadata| match metadata.cursor(&name) {
+                    Some(cursor) => Ok((cursor.len(), false)),
+                    None => Err(crate::not_registered(&name).into()),
+                })?;
+                self.consumer = Some(name);
+                self.in_flight = InFlight::default();
+                Ok(Some(Response::Subscribed { unread }))
+            }
+            Request::Seek { timestamp } => {
+                let name = self.subscribed()?;
+                let file = &self.file;
+                let skipped = self.with_metadata(|metadata| {
+                    let mut skipped = 0;
+                    while let Some(sample) = peek_sample(file, metadata, &name, skipped)? {
+                        if sample.timestamp() >= timestamp {
+                            break;
+                        }
+                        skipped += 1;
+                    }
+                    metadata.skip(&name, skipped)?;
+                    Ok((skipped, skipped > 0))
+                })?;
+                self.in_flight = InFlight::default();
+                Ok(Some(Response::Sought { skipped }))
+            }
+            Request::Ack { count } => {
+                let name = self.subscribed()?;
+                let count = count as u64;
+                let mut in_flight = self.in_flight;
+                self.with_metadata(|metadata| {
+                    in_flight.follow(metadata, &name);
+                    let sent = in_flight.overwritten + in_flight.count;
+                    if count > sent {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("only {} samples are waiting for an ack", sent),
+                        )
+                        .into());
+                    }
+                    // the oldest samples sent are the overwritten ones
+                    let overwritten = count.min(in_flight.overwritten);
+                    in_flight.overwritten -= overwritten;
+                    metadata.skip(&name, count - overwritten)?;
+                    in_flight.count -= count - overwritten;
+                    // moved by the ack, not by the producer
+                    in_flight.start = metadata.cursor(&name).map_or(0, |c| c.read_head);
+                    Ok(((), count > overwritten))
+                })?;
+                self.in_flight = in_flight;
+                // the samples that follow are the answer
+                Ok(None)
+            }
+        }
+    }
+
+    // sends the unread samples that are not in flight yet
+    fn send_unread(&mut self) -> Result<(), SensorDataError> {
+        let Some(name) = self.consumer.clone() else {
+            return Ok(());
+        };
+        let mut in_flight = self.in_flight;
+        let file = &self.file;
+        let samples = self.with_metadata(|metadata| {
+            in_flight.follow(metadata, &name);
+            let mut samples: Vec<SensorData> = vec![];
+            while in_flight.count + (samples.len() as u64) < MAX_IN_FLIGHT {
+                match peek_sample(
+                    file,
+                    metadata,
+                    &name,
+                    in_flight.count + samples.len() as u64,
+                )? {
+                    Some(sample) => samples.push(sample),
+                    None => break,
+                }
+            }
+            Ok((samples, false))
+        })?;
+        self.in_flight = in_flight;
+        for sample in samples {
+            self.send(&Response::Sample(sample))?;
+            self.in_flight.count += 1;
+        }
+        Ok(())
+    }
+}
+
+fn read_requests(stream: Stream, wakeups: Sender<Wakeup>) {
+    let mut reader = FramedReader::new(stream);
+    loop {
+        let wakeup = match reader.read() {
+            Ok(request) => Wakeup::Request(request),
+            Err(BinaryIoError::Eof) => Wakeup::Closed(None),
+            Err(e) => Wakeup::Closed(Some(e)),
+        };
+        let closed = matches!(wakeup, Wakeup::Closed(_));
+        if wakeups.send(wakeup).is_err() || closed {
+            return;
+        }
+    }
+}
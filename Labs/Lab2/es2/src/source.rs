@@ -0,0 +1,321 @@
+use crate::{unix_time, SensorData, SensorDataError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+// where the producer gets its samples from
+pub trait SensorSource {
+    // None once there is nothing left, as at the end of a replayed file
+    fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError>;
+}
+
+// generated samples are one second apart from `start`, or take the time they are generated at
+fn generated_at(start: Option<u32>, index: u64) -> u32 {
+    match start {
+        Some(start) => start.wrapping_add(index as u32),
+        None => unix_time(),
+    }
+}
+
+fn seeded(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// uniform values in [0, 1), as simulate_sensor
+pub struct RandomSource {
+    rng: StdRng,
+    sensors: u32,
+    start: Option<u32>,
+    index: u64,
+}
+
+impl RandomSource {
+    pub fn new(sensors: u32, seed: Option<u64>, start: Option<u32>) -> Self {
+        RandomSource {
+            rng: seeded(seed),
+            sensors: sensors.max(1),
+            start,
+            index: 0,
+        }
+    }
+}
+
+impl SensorSource for RandomSource {
+    fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError> {
+        let mut values = [0.0; 10];
+        for v in &mut values {
+            *v = self.rng.gen::<f32>();
+        }
+        let seq = (self.index % self.sensors as u64) as u32;
+        let sample = SensorData::new(seq, values, generated_at(self.start, self.index));
+        self.index += 1;
+        Ok(Some(sample))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Waveform {
+    // between 0 and 1, 0.5 at the start of the period
+    Sine,
+    // 0 for the first half of the period and 1 for the second
+    Step,
+    // from 0 up to 1 over the period
+    Ramp,
+}
+
+impl Waveform {
+    // `phase` is the position in the period, in [0, 1)
+    fn at(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => 0.5 + 0.5 * (2.0 * std::f32::consts::PI * phase).sin(),
+            Waveform::Step if phase < 0.5 => 0.0,
+            Waveform::Step => 1.0,
+            Waveform::Ramp => phase,
+        }
+    }
+}
+
+// every sensor follows the waveform with one sample per step, the ten values of a sample are
+// shifted by a tenth of the period from each other. the noise comes from the seed only, so the
+// same arguments always give the same samples
+pub struct WaveformSource {
+    waveform: Waveform,
+    sensors: u32,
+    period: u32,
+    noise: f32,
+    rng: StdRng,
+    start: Option<u32>,
+    index: u64,
+}
+
+impl WaveformSource {
+    pub fn new(
+        waveform: Waveform,
+        sensors: u32,
+        period: u32,
+        noise: f32,
+        seed: Option<u64>,
+        start: Option<u32>,
+    ) -> Self {
+        WaveformSource {
+            waveform,
+            sensors: sensors.max(1),
+            period: period.max(1),
+            noise,
+            rng: seeded(seed),
+            start,
+            index: 0,
+        }
+    }
+}
+
+impl SensorSource for WaveformSource {
+    fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError> {
+        let seq = (self.index % self.sensors as u64) as u32;
+        let step = self.index / self.sensors as u64;
+        let mut values = [0.0; 10];
+        for (i, v) in values.iter_mut().enumerate() {
+            let phase =
+                ((step % self.period as u64) as f32 / self.period as f32 + i as f32 / 10.0).fract();
+            *v = self.waveform.at(phase);
+            if self.noise > 0.0 {
+                *v += self.rng.gen_range(-self.noise..=self.noise);
+            }
+        }
+        let sample = SensorData::new(seq, values, generated_at(self.start, self.index));
+        self.index += 1;
+        Ok(Some(sample))
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(
+    field: &str,
+    what: &str,
+    line: usize,
+) -> Result<T, SensorDataError> {
+    field.trim().parse().map_err(|_| {
+        SensorDataError::SourceError(format!("line {}: invalid {} {:?}", line, what, field))
+    })
+}
+
+fn parse_values<'a>(
+    fields: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<[f32; 10], SensorDataError> {
+    let fields: Vec<_> = fields.collect();
+    if fields.len() != 10 {
+        return Err(SensorDataError::SourceError(format!(
+            "line {}: expected 10 values, found {}",
+            line,
+            fields.len()
+        )));
+    }
+    let mut values = [0.0; 10];
+    for (v, field) in values.iter_mut().zip(fields) {
+        *v = parse_field(field, "value", line)?;
+    }
+    Ok(values)
+}
+
+// replays recorded samples, one `timestamp,seq,val0,..,val9` line each. the timestamps are kept,
+// a header line and empty lines are skipped
+pub struct CsvSource<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> CsvSource<R> {
+    pub fn new(reader: R) -> Self {
+        CsvSource {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> SensorSource for CsvSource<R> {
+    fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError> {
+        for text in self.lines.by_ref() {
+            let text = text?;
+            self.line += 1;
+            let text = text.trim();
+            if text.is_empty() || (self.line == 1 && text.starts_with("timestamp")) {
+                continue;
+            }
+            let mut fields = text.split(',');
+            let timestamp = parse_field(fields.next().unwrap_or(""), "timestamp", self.line)?;
+            let seq = parse_field(fields.next().unwrap_or(""), "sensor", self.line)?;
+            let values = parse_values(fields, self.line)?;
+            return Ok(Some(SensorData::new(seq, values, timestamp)));
+        }
+        Ok(None)
+    }
+}
+
+// ten values per line separated by commas or spaces, as another program writes them. the sensors
+// take turns and the samples are timestamped when read
+pub struct LineSource<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    sensors: u32,
+    next: u32,
+}
+
+impl<R: BufRead> LineSource<R> {
+    pub fn new(reader: R, sensors: u32) -> Self {
+        LineSource {
+            lines: reader.lines(),
+            line: 0,
+            sensors: sensors.max(1),
+            next: 0,
+        }
+    }
+}
+
+impl<R: BufRead> SensorSource for LineSource<R> {
+    fn next_sample(&mut self) -> Result<Option<SensorData>, SensorDataError> {
+        for text in self.lines.by_ref() {
+            let text = text?;
+            self.line += 1;
+            let fields = text
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|f| !f.is_empty());
+            if fields.clone().next().is_none() {
+                continue;
+            }
+            let values = parse_values(fields, self.line)?;
+            let seq = self.next;
+            self.next = (self.next + 1) % self.sensors;
+            return Ok(Some(SensorData::new(seq, values, unix_time())));
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum SourceKind {
+    Random,
+    Sine,
+    Step,
+    Ramp,
+    /// replays --input
+    Csv,
+    /// reads values from stdin
+    Stdin,
+}
+
+#[derive(clap::Args)]
+pub struct SourceArgs {
+    #[clap(long, value_enum, default_value = "random")]
+    pub source: SourceKind,
+    /// makes random and the waveform noise reproducible
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// waveform period in samples of each sensor
+    #[clap(long, default_value = "20")]
+    pub period: u32,
+    /// the waveforms get uniform noise in [-noise, noise] added
+    #[clap(long, default_value = "0")]
+    pub noise: f32,
+    /// timestamp of the first generated sample, the next ones are one second apart. without it
+    /// samples are timestamped when they are generated
+    #[clap(long)]
+    pub start: Option<u32>,
+    /// csv file to replay, - for stdin
+    #[clap(long, default_value = "-")]
+    pub input: String,
+    /// pause between two samples, 1000 by default. none for stdin, whose lines already come at
+    /// the pace they are typed or piped
+    #[clap(long)]
+    pub interval_ms: Option<u64>,
+    /// replays at the pace of the sample timestamps instead of every --interval-ms
+    #[clap(long)]
+    pub realtime: bool,
+    /// stops after this many samples, by default when the source runs out
+    #[clap(long)]
+    pub count: Option<u64>,
+}
+
+impl SourceArgs {
+    pub fn open(&self, sensors: u32) -> Result<Box<dyn SensorSource>, SensorDataError> {
+        let waveform = |waveform| {
+            Box::new(WaveformSource::new(
+                waveform,
+                sensors,
+                self.period,
+                self.noise,
+                self.seed,
+                self.start,
+            ))
+        };
+        Ok(match self.source {
+            SourceKind::Random => Box::new(RandomSource::new(sensors, self.seed, self.start)),
+            SourceKind::Sine => waveform(Waveform::Sine),
+            SourceKind::Step => waveform(Waveform::Step),
+            SourceKind::Ramp => waveform(Waveform::Ramp),
+            SourceKind::Csv if self.input == "-" => {
+                Box::new(CsvSource::new(std::io::stdin().lock()))
+            }
+            SourceKind::Csv => Box::new(CsvSource::new(BufReader::new(File::open(&self.input)?))),
+            SourceKind::Stdin => Box::new(LineSource::new(std::io::stdin().lock(), sensors)),
+        })
+    }
+
+    // how long to wait between writing `previous` and `next`
+    pub fn pause(&self, previous: &SensorData, next: &SensorData) -> Duration {
+        if self.realtime {
+            Duration::from_secs(next.timestamp().saturating_sub(previous.timestamp()) as u64)
+        } else {
+            let default = match self.source {
+                SourceKind::Stdin => 0,
+                _ => 1000,
+            };
+            Duration::from_millis(self.interval_ms.unwrap_or(default))
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::SensorData;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+// a duration as 30s, 5m, 1h or plain seconds
+pub(crate) fn parse_seconds(text: &str) -> Option<u32> {
+    let (number, unit) = match text.char_indices().last()? {
+        (i, 's') => (&text[..i], 1),
+        (i, 'm') => (&text[..i], 60),
+        (i, 'h') => (&text[..i], 3600),
+        _ => (text, 1),
+    };
+    number.parse::<u32>().ok()?.checked_mul(unit)
+}
+
+pub(crate) fn format_seconds(seconds: u32) -> String {
+    match seconds {
+        s if s > 0 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s > 0 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+// what the statistics of a sensor are computed over
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Window {
+    // the last samples of the sensor
+    Count(usize),
+    // the samples of the sensor whose timestamp is less than this many seconds before the newest
+    Time(u32),
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    // a number of samples, or a duration as 30s, 5m or 1h
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let window = match text.parse::<usize>() {
+            Ok(count) => Window::Count(count),
+            Err(_) => Window::Time(
+                parse_seconds(text).ok_or_else(|| format!("invalid window {:?}", text))?,
+            ),
+        };
+        if matches!(window, Window::Count(0) | Window::Time(0)) {
+            return Err("the window cannot be empty".to_string());
+        }
+        Ok(window)
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Window::Count(count) => write!(f, "{} samples", count),
+            Window::Time(seconds) => write!(f, "{}", format_seconds(*seconds)),
+        }
+    }
+}
+
+// statistics of the sample averages in the window of one sensor
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowStats {
+    pub count: usize,
+    pub mean: f32,
+    pub stddev: f32,
+    pub min: f32,
+    pub max: f32,
+    // change per second from the oldest to the newest sample, None while they share a timestamp
+    pub rate: Option<f32>,
+    sorted: Vec<f32>,
+}
+
+impl WindowStats {
+    fn of(samples: &VecDeque<(u32, f32)>) -> Self {
+        let count = samples.len();
+        let mean = samples.iter().map(|&(_, v)| v as f64).sum::<f64>() / count as f64;
+        let variance = samples
+            .iter()
+            .map(|&(_, v)| (v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        let mut sorted: Vec<f32> = samples.iter().map(|&(_, v)| v).collect();
+        sorted.sort_by(f32::total_cmp);
+        let (first, last) = (samples[0], samples[count - 1]);
+        let rate =
+            (last.0 != first.0).then(|| (last.1 - first.1) / (last.0 as f32 - first.0 as f32));
+        WindowStats {
+            count,
+            mean: mean as f32,
+            stddev: variance.sqrt() as f32,
+            min: sorted[0],
+            max: sorted[count - 1],
+            rate,
+            sorted,
+        }
+    }
+
+    // nearest rank, p in [0, 100]
+    pub fn percentile(&self, p: f32) -> f32 {
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.count as f32).ceil() as usize;
+        self.sorted[rank.clamp(1, self.count) - 1]
+    }
+}
+
+impl fmt::Display for WindowStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n => {}, mean => {:.06}, stddev => {:.06}, p50 => {:.06}, p90 => {:.06}, p99 => {:.06}, rate => ",
+            self.count,
+            self.mean,
+            self.stddev,
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0)
+        )?;
+        match self.rate {
+            Some(rate) => write!(f, "{:.06}/s", rate),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+// rolling window of every sensor, by seq
+pub struct SensorWindows {
+    window: Window,
+    samples: BTreeMap<u32, VecDeque<(u32, f32)>>,
+}
+
+impl SensorWindows {
+    pub fn new(window: Window) -> Self {
+        SensorWindows {
+            window,
+            samples: BTreeMap::new(),
+        }
+    }
+
+    // adds the average of the sample to the window of its sensor, returns the statistics of it
+    pub fn push(&mut self, sample: &SensorData) -> WindowStats {
+        let samples = self.samples.entry(sample.seq()).or_default();
+        samples.push_back((sample.timestamp(), sample.avg()));
+        match self.window {
+            Window::Count(count) => {
+                while samples.len() > count.max(1) {
+                    samples.pop_front();
+                }
+            }
+            Window::Time(seconds) => {
+                let newest = sample.timestamp();
+                // the newest sample always stays
+                while samples.len() > 1
+                    && samples
+                        .front()
+                        .is_some_and(|&(t, _)| newest.saturating_sub(t) >= seconds)
+                {
+                    samples.pop_front();
+                }
+            }
+        }
+        WindowStats::of(samples)
+    }
+
+    pub fn stats(&self, seq: u32) -> Option<WindowStats> {
+        self.samples
+            .get(&seq)
+            .filter(|s| !s.is_empty())
+            .map(WindowStats::of)
+    }
+}
@@ -0,0 +1,150 @@
+use sensors::{
+    load_rules, AlertState, Alerts, Comparison, Metric, Rule, SensorData, SensorWindows, Window,
+};
+
+// a sample whose values all equal `value`, so that its average is `value`
+fn sample(seq: u32, value: f32, timestamp: u32) -> SensorData {
+    SensorData::new(seq, [value; 10], timestamp)
+}
+
+#[test]
+fn windows_are_parsed() {
+    assert_eq!("50".parse(), Ok(Window::Count(50)));
+    assert_eq!("30s".parse(), Ok(Window::Time(30)));
+    assert_eq!("5m".parse(), Ok(Window::Time(300)));
+    assert_eq!("1h".parse(), Ok(Window::Time(3600)));
+    assert!("0".parse::<Window>().is_err());
+    assert!("soon".parse::<Window>().is_err());
+}
+
+#[test]
+fn count_window() {
+    let mut windows = SensorWindows::new(Window::Count(4));
+    for (i, value) in [9.0, 1.0, 2.0, 3.0, 4.0].into_iter().enumerate() {
+        windows.push(&sample(0, value, i as u32 * 2));
+        // other sensors have windows of their own
+        windows.push(&sample(1, 100.0, i as u32 * 2));
+    }
+    let stats = windows.stats(0).unwrap();
+    assert_eq!(stats.count, 4);
+    assert_eq!((stats.min, stats.max, stats.mean), (1.0, 4.0, 2.5));
+    assert!((stats.stddev - 1.25f32.sqrt()).abs() < 1e-6);
+    assert_eq!((stats.percentile(50.0), stats.percentile(90.0)), (2.0, 4.0));
+    assert_eq!(stats.percentile(0.0), 1.0);
+    // from 1 at t=2 to 4 at t=8
+    assert_eq!(stats.rate, Some(0.5));
+    assert!(windows.stats(2).is_none());
+}
+
+#[test]
+fn time_window() {
+    let mut windows = SensorWindows::new(Window::Time(10));
+    windows.push(&sample(0, 5.0, 100));
+    let stats = windows.push(&sample(0, 1.0, 105));
+    assert_eq!(stats.count, 2);
+    assert_eq!(stats.rate, Some(-0.8));
+    let stats = windows.push(&sample(0, 2.0, 110));
+    assert_eq!((stats.count, stats.mean), (2, 1.5));
+    let stats = windows.push(&sample(0, 2.0, 200));
+    assert_eq!((stats.count, stats.rate), (1, None));
+}
+
+#[test]
+fn rules_are_parsed() {
+    let rule: Rule = "sensor 3 avg > 0.9 for 30s".parse().unwrap();
+    assert_eq!(
+        rule,
+        Rule {
+            sensor: Some(3),
+            metric: Metric::Avg,
+            comparison: Comparison::Greater,
+            threshold: 0.9,
+            duration: 30,
+        }
+    );
+    assert_eq!(rule.to_string(), "sensor 3 avg > 0.9 for 30s");
+    let rule: Rule = "sensor * p95 <= -1".parse().unwrap();
+    assert_eq!((rule.sensor, rule.metric), (None, Metric::Percentile(95.0)));
+    assert_eq!(rule.to_string(), "sensor * p95 <= -1");
+    assert_eq!(
+        "sensor 1 rate >= 2 for 120"
+            .parse::<Rule>()
+            .unwrap()
+            .to_string(),
+        "sensor 1 rate >= 2 for 2m"
+    );
+
+    let config = "# hot sensors\n\nsensor 3 avg > 0.9 for 30s\n  sensor * stddev > 0.2\n";
+    assert_eq!(load_rules(config.as_bytes()).unwrap().len(), 2);
+    let error = load_rules("sensor 1 avg > 1\nsensor 1 median > 1\n".as_bytes()).unwrap_err();
+    assert!(format!("{:?}", error).contains("line 2: unknown metric \\\"median\\\""));
+    for invalid in [
+        "sensor x avg > 1",
+        "sensor 1 avg = 1",
+        "sensor 1 avg > high",
+        "sensor 1 avg > 1 for ever",
+        "avg > 1",
+        "sensor 1 p101 > 1",
+    ] {
+        assert!(invalid.parse::<Rule>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn alert_fires_after_its_duration_and_resolves() {
+    let rules = load_rules("sensor 3 avg > 0.9 for 30s".as_bytes()).unwrap();
+    let mut alerts = Alerts::new(rules);
+    let mut windows = SensorWindows::new(Window::Count(10));
+    let mut feed = |seq, value, timestamp| {
+        let sample = sample(seq, value, timestamp);
+        let stats = windows.push(&sample);
+        alerts.check(&sample, &stats)
+    };
+    assert!(feed(3, 0.95, 0).is_empty());
+    assert!(feed(3, 0.95, 20).is_empty());
+    // a dip starts the duration again
+    assert!(feed(3, 0.5, 25).is_empty());
+    assert!(feed(3, 0.95, 30).is_empty());
+    // other sensors are not watched
+    assert!(feed(4, 1.0, 60).is_empty());
+    let events = feed(3, 0.92, 60);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].state, AlertState::Firing);
+    assert_eq!((events[0].since, events[0].timestamp), (30, 60));
+    // fires once while the condition holds
+    assert!(feed(3, 0.99, 70).is_empty());
+    let events = feed(3, 0.5, 80);
+    assert_eq!(events[0].state, AlertState::Resolved);
+    assert_eq!(
+        serde_json::to_string(&events[0]).unwrap(),
+        r#"{"rule":"sensor 3 avg > 0.9 for 30s","state":"resolved","sensor":3,"value":0.5,"since":30,"timestamp":80}"#
+    );
+    assert!(feed(3, 0.1, 90).is_empty());
+}
+
+#[test]
+fn window_metrics_in_alerts() {
+    let rules = load_rules("sensor * rate > 0.01\nsensor * mean < 0.2\n".as_bytes()).unwrap();
+    let mut alerts = Alerts::new(rules);
+    let mut windows = SensorWindows::new(Window::Time(60));
+    let mut feed = |seq, value, timestamp| {
+        let sample = sample(seq, value, timestamp);
+        let stats = windows.push(&sample);
+        alerts.check(&sample, &stats)
+    };
+    let events = feed(0, 0.1, 0);
+    // the rate of a single sample is not known
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].rule, "sensor * mean < 0.2");
+    let events = feed(0, 0.5, 10);
+    let rules: Vec<_> = events.iter().map(|e| (e.rule.as_str(), e.state)).collect();
+    assert_eq!(
+        rules,
+        [
+            ("sensor * rate > 0.01", AlertState::Firing),
+            ("sensor * mean < 0.2", AlertState::Resolved)
+        ]
+    );
+    // each sensor has its own state
+    assert_eq!(feed(1, 0.1, 10).len(), 1);
+}
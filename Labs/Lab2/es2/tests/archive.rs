@@ -0,0 +1,190 @@
+use sensors::{
+    read_segment, segments, segments_between, Archive, ArchiveFormat, CsvSource, Rotation,
+    SensorData, SensorSource,
+};
+use std::path::{Path, PathBuf};
+
+fn sample(seq: u32, timestamp: u32) -> SensorData {
+    let mut values = [0.0; 10];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = (timestamp as f32 + i as f32) / 7.0;
+    }
+    SensorData::new(seq, values, timestamp)
+}
+
+fn read_all(dir: &Path) -> Vec<SensorData> {
+    let mut samples = vec![];
+    for path in segments(dir).unwrap() {
+        read_segment(&path, |sample| {
+            samples.push(sample);
+            Ok(())
+        })
+        .unwrap();
+    }
+    samples
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    segments(dir)
+        .unwrap()
+        .iter()
+        .map(|path: &PathBuf| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn rotations_are_parsed() {
+    assert_eq!("hourly".parse(), Ok(Rotation::Hourly));
+    assert_eq!("4096".parse(), Ok(Rotation::Size(4096)));
+    assert_eq!("64K".parse(), Ok(Rotation::Size(64 << 10)));
+    assert_eq!("1G".parse(), Ok(Rotation::Size(1 << 30)));
+    for invalid in ["0", "M", "12X", "daily"] {
+        assert!(invalid.parse::<Rotation>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn hourly_csv_segments_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let written: Vec<_> = (7190..7210).map(|t| sample(t % 3, t)).collect();
+    let mut archive = Archive::new(
+        dir.path(),
+        ArchiveFormat::Csv,
+        Rotation::Hourly,
+        false,
+        None,
+    )
+    .unwrap();
+    for sample in &written {
+        archive.write(sample).unwrap();
+    }
+    drop(archive);
+    // 7200 starts the next hour
+    assert_eq!(names(dir.path()), ["sensors-7190.csv", "sensors-7200.csv"]);
+    assert_eq!(read_all(dir.path()), written);
+
+    // the producer can replay a segment
+    let segment = std::fs::File::open(dir.path().join("sensors-7200.csv")).unwrap();
+    let mut source = CsvSource::new(std::io::BufReader::new(segment));
+    assert_eq!(source.next_sample().unwrap(), Some(written[10]));
+}
+
+#[test]
+fn segments_out_of_a_time_range_are_pruned_by_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut archive = Archive::new(
+        dir.path(),
+        ArchiveFormat::Csv,
+        Rotation::Hourly,
+        false,
+        None,
+    )
+    .unwrap();
+    for t in (3600..4 * 3600).step_by(600) {
+        archive.write(&sample(0, t)).unwrap();
+    }
+    drop(archive);
+    let between = |since, until| -> Vec<String> {
+        segments_between(dir.path(), since, until)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+    assert_eq!(between(None, None), names(dir.path()));
+    assert_eq!(
+        between(Some(7201), None),
+        ["sensors-7200.csv", "sensors-10800.csv"]
+    );
+    // a segment may end with samples of the second the next one starts in
+    assert_eq!(
+        between(Some(7200), Some(7201)),
+        ["sensors-3600.csv", "sensors-7200.csv"]
+    );
+    assert_eq!(between(None, Some(7200)), ["sensors-3600.csv"]);
+    assert_eq!(between(Some(20000), None), ["sensors-10800.csv"]);
+    assert!(between(None, Some(3600)).is_empty());
+}
+
+#[test]
+fn size_rotation_and_retention() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut archive = Archive::new(
+        dir.path(),
+        ArchiveFormat::Csv,
+        Rotation::Size(1000),
+        true,
+        Some(2),
+    )
+    .unwrap();
+    let written: Vec<_> = (100..400).map(|t| sample(0, t)).collect();
+    for batch in written.chunks(10) {
+        for sample in batch {
+            archive.write(sample).unwrap();
+        }
+        archive.flush().unwrap();
+    }
+    drop(archive);
+    let names = names(dir.path());
+    assert_eq!(names.len(), 2);
+    assert!(names.iter().all(|n| n.ends_with(".csv.gz")));
+    // only the newest samples are left
+    let read = read_all(dir.path());
+    assert!(!read.is_empty() && read.len() < written.len());
+    assert_eq!(read, written[written.len() - read.len()..]);
+}
+
+#[test]
+fn unfinished_gzip_segment_is_read_up_to_the_last_flush() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut archive =
+        Archive::new(dir.path(), ArchiveFormat::Csv, Rotation::Hourly, true, None).unwrap();
+    for t in 0..5 {
+        archive.write(&sample(1, t)).unwrap();
+    }
+    archive.flush().unwrap();
+    archive.write(&sample(1, 5)).unwrap();
+    assert_eq!(read_all(dir.path()).len(), 5);
+    archive.close().unwrap();
+    assert_eq!(read_all(dir.path()).len(), 6);
+    // a segment starting in the same second gets a counter
+    archive.write(&sample(1, 0)).unwrap();
+    drop(archive);
+    assert_eq!(names(dir.path()), ["sensors-0.csv.gz", "sensors-0-1.csv.gz"]);
+    assert_eq!(read_all(dir.path()).len(), 7);
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_segments_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    // within an hour, above i32::MAX as parquet has no unsigned columns
+    let start = (u32::MAX / 3600 - 1) * 3600;
+    let written: Vec<_> = (0..3000).map(|t| sample(t % 10, start + t)).collect();
+    for gzip in [false, true] {
+        let mut archive = Archive::new(
+            dir.path(),
+            ArchiveFormat::Parquet,
+            Rotation::Hourly,
+            gzip,
+            None,
+        )
+        .unwrap();
+        for batch in written.chunks(100) {
+            for sample in batch {
+                archive.write(sample).unwrap();
+            }
+            archive.flush().unwrap();
+        }
+        let current = archive.current().unwrap().to_path_buf();
+        drop(archive);
+        let mut read = vec![];
+        read_segment(&current, |sample| {
+            read.push(sample);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(read, written);
+        std::fs::remove_file(current).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+use binary_io::BinPack;
+use sensors::{
+    fsck, read_metadata, read_sample, record_offset, simulate_sensor, upgrade_baseline,
+    write_metadata, write_sample, FsckOptions, FsckReport, FullPolicy, SensorDataError,
+    SensorFileMetadata, DATA_OFFSET, DEFAULT_CONSUMER, METADATA_SLOT_SIZE, SENSOR_DATA_SIZE,
+};
+use std::fs::File;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+
+const CHECK: FsckOptions = FsckOptions {
+    repair: false,
+    samples: None,
+};
+const REPAIR: FsckOptions = FsckOptions {
+    repair: true,
+    samples: None,
+};
+
+// a file with `written` samples in a buffer of `size`, nothing read yet
+fn ring(size: u64, written: u32) -> File {
+    let file = tempfile::tempfile().unwrap();
+    let mut metadata = SensorFileMetadata::from_size(size);
+    metadata.policy = FullPolicy::OverwriteOldest;
+    write_metadata(&file, &mut metadata).unwrap();
+    for seq in 0..written {
+        assert!(write_sample(&file, &mut metadata, &simulate_sensor(seq)).unwrap());
+        write_metadata(&file, &mut metadata).unwrap();
+    }
+    file
+}
+
+fn unread(file: &File) -> u64 {
+    let metadata = read_metadata(file).unwrap().unwrap();
+    metadata.cursor(DEFAULT_CONSUMER).unwrap().len()
+}
+
+fn overwrite(mut file: &File, offset: u64, bytes: &[u8]) {
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+fn check_and_repair(file: &File) -> FsckReport {
+    let found = fsck(file, &CHECK).unwrap();
+    assert!(!found.is_clean());
+    assert!(!found.all_repaired());
+    let repaired = fsck(file, &REPAIR).unwrap();
+    assert_eq!(repaired.findings.len(), found.findings.len());
+    assert!(repaired.all_repaired());
+    assert!(fsck(file, &CHECK).unwrap().is_clean());
+    repaired
+}
+
+#[test]
+fn generations_alternate_between_the_slots() {
+    let file = tempfile::tempfile().unwrap();
+    let mut metadata = SensorFileMetadata::from_size(4);
+    for generation in 1..=3 {
+        write_metadata(&file, &mut metadata).unwrap();
+        assert_eq!(metadata.generation(), generation);
+        assert_eq!(
+            read_metadata(&file).unwrap().unwrap().generation(),
+            generation
+        );
+    }
+}
+
+#[test]
+fn torn_metadata_write_falls_back_to_the_previous_slot() {
+    let file = ring(4, 3);
+    let newest = read_metadata(&file).unwrap().unwrap();
+    // the crash hit the middle of the newest slot
+    let slot = newest.generation() % 2;
+    overwrite(&file, slot * METADATA_SLOT_SIZE + 100, &[0xee; 20]);
+    let metadata = read_metadata(&file).unwrap().unwrap();
+    assert_eq!(metadata.generation(), newest.generation() - 1);
+    assert_eq!(unread(&file), 2);
+
+    let report = check_and_repair(&file);
+    assert!(report.findings[0].message.contains("slot"));
+    assert_eq!(unread(&file), 2);
+}
+
+#[test]
+fn fresh_files_are_clean() {
+    assert!(fsck(&tempfile::tempfile().unwrap(), &CHECK)
+        .unwrap()
+        .is_clean());
+    assert!(fsck(&ring(4, 0), &CHECK).unwrap().is_clean());
+    assert!(fsck(&ring(4, 6), &CHECK).unwrap().is_clean());
+}
+
+#[test]
+fn torn_record_at_the_end() {
+    let file = ring(4, 2);
+    // the producer died while writing the third sample
+    let sample = simulate_sensor(2).to_vec().unwrap();
+    overwrite(&file, record_offset(2), &sample[..10]);
+    let report = check_and_repair(&file);
+    assert!(report.findings[0].message.contains("10 bytes"));
+    assert_eq!(file.metadata().unwrap().len(), record_offset(2));
+    assert_eq!(unread(&file), 2);
+}
+
+#[test]
+fn damaged_unread_samples_are_dropped() {
+    let file = ring(8, 5);
+    // the third unread sample cannot be decoded anymore
+    overwrite(&file, record_offset(2), &[0xff; 6]);
+    let report = check_and_repair(&file);
+    assert!(report.findings[0].message.contains("3 samples dropped"));
+    assert_eq!(unread(&file), 2);
+}
+
+#[test]
+fn inconsistent_cursor_is_reset() {
+    let file = ring(4, 2);
+    let mut metadata = read_metadata(&file).unwrap().unwrap();
+    metadata.register("late").unwrap();
+    // a write head that moved without the cursors, as no correct write can produce
+    metadata.write_head = 3;
+    write_metadata(&file, &mut metadata).unwrap();
+    let report = check_and_repair(&file);
+    assert_eq!(report.findings.len(), 2);
+    assert_eq!(unread(&file), 0);
+}
+
+#[test]
+fn unreadable_metadata_is_rebuilt() {
+    let file = ring(4, 3);
+    overwrite(&file, 0, &[0xee; DATA_OFFSET as usize]);
+    assert!(read_metadata(&file).is_err());
+    check_and_repair(&file);
+    let metadata = read_metadata(&file).unwrap().unwrap();
+    // the size is guessed from the records in the file
+    assert_eq!(metadata.buffer_size(), 3);
+    assert_eq!(
+        file.metadata().unwrap().len(),
+        DATA_OFFSET + 3 * SENSOR_DATA_SIZE
+    );
+}
+
+#[test]
+fn unknown_buffer_size_cannot_be_repaired() {
+    let file = tempfile::tempfile().unwrap();
+    overwrite(&file, 0, &[0xee; 10]);
+    let report = fsck(&file, &REPAIR).unwrap();
+    assert!(!report.all_repaired());
+    let with_size = FsckOptions {
+        repair: true,
+        samples: Some(5),
+    };
+    assert!(fsck(&file, &with_size).unwrap().all_repaired());
+    assert_eq!(read_metadata(&file).unwrap().unwrap().buffer_size(), 5);
+}
+
+// a file as the first release wrote it: four native u64 and records of 48 bytes, no version header
+fn baseline(path: &std::path::Path, heads: [u64; 4], samples: u32) {
+    let mut bytes = vec![];
+    for value in heads {
+        bytes.extend(value.to_ne_bytes());
+    }
+    for seq in 0..samples {
+        bytes.extend(seq.to_ne_bytes());
+        for value in 0..10 {
+            bytes.extend((value as f32).to_ne_bytes());
+        }
+        bytes.extend((1000 + seq).to_ne_bytes());
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn baseline_files_are_upgraded_by_a_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sensor_data.bin");
+    // a buffer of 3 with 2 samples written and the first one read
+    baseline(&path, [1, 2, 3, 1], 2);
+    let file = File::open(&path).unwrap();
+    match read_metadata(&file) {
+        Err(SensorDataError::IoError(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    let report = fsck(&file, &REPAIR).unwrap();
+    assert_eq!(report.findings.len(), 1);
+    assert!(!report.all_repaired());
+
+    assert!(upgrade_baseline(&path).unwrap());
+    let file = File::open(&path).unwrap();
+    assert!(fsck(&file, &CHECK).unwrap().is_clean());
+    let mut metadata = read_metadata(&file).unwrap().unwrap();
+    assert_eq!(metadata.buffer_size(), 3);
+    assert_eq!(metadata.write_head, 2);
+    assert_eq!(metadata.policy, FullPolicy::DropNewest);
+    assert_eq!(metadata.cursors().count(), 1);
+    let sample = read_sample(&file, &mut metadata, DEFAULT_CONSUMER)
+        .unwrap()
+        .unwrap();
+    assert_eq!((sample.seq(), sample.timestamp()), (1, 1001));
+    assert_eq!(sample.values()[9], 9.0);
+    assert!(read_sample(&file, &mut metadata, DEFAULT_CONSUMER)
+        .unwrap()
+        .is_none());
+
+    // nothing left to upgrade, and no copy left behind
+    assert!(!upgrade_baseline(&path).unwrap());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
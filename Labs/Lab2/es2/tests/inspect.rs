@@ -0,0 +1,40 @@
+use binary_io::inspect::{inspect, Layout};
+use binary_io::BinPack;
+use es1::{CValue, SValueStruct, ValueStruct};
+use sensors::schema_registry;
+
+#[test]
+fn es1_files_can_be_inspected() {
+    let mut message = [0; 21];
+    message[0] = b'h' as _;
+    let values = [
+        CValue::Value(ValueStruct {
+            value_type: 1,
+            val: 1.5,
+            timestamp: 10,
+        }),
+        CValue::SValue(SValueStruct {
+            value_type: 3,
+            message,
+        }),
+    ];
+    let mut bytes = vec![];
+    for value in &values {
+        bytes.extend(value.to_vec().unwrap());
+    }
+
+    let registry = schema_registry();
+    let layout = Layout {
+        header: None,
+        record: registry.get("export-data").unwrap(),
+        count: None,
+        endian: None,
+    };
+    let mut out = vec![];
+    let report = inspect(&mut out, &bytes, &layout).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(report.records, 2, "{}", out);
+    assert_eq!(report.errors, 0, "{}", out);
+    assert!(report.truncated.is_none() && report.trailing.is_none());
+    assert!(out.contains("val: 1.5"), "{}", out);
+}
@@ -0,0 +1,164 @@
+use binary_io::BinPack;
+use sensors::{
+    read_metadata, read_sample, sensor_lock_file, sensor_unlock_file, simulate_sensor,
+    write_metadata, write_sample, FullPolicy, SensorFileMetadata, DATA_OFFSET, DEFAULT_CONSUMER,
+    MAX_CONSUMERS,
+};
+use std::io::{Seek, SeekFrom};
+
+fn fill(metadata: &mut SensorFileMetadata, count: usize) {
+    for _ in 0..count {
+        metadata.advance_write_head().unwrap();
+    }
+}
+
+fn read_head(metadata: &SensorFileMetadata, name: &str) -> (u64, u64) {
+    let cursor = metadata.cursor(name).unwrap();
+    (cursor.read_head, cursor.len())
+}
+
+#[test]
+fn drop_newest_and_block_refuse_a_full_buffer() {
+    for policy in [FullPolicy::DropNewest, FullPolicy::Block] {
+        let mut metadata = SensorFileMetadata::from_size(3);
+        metadata.policy = policy;
+        fill(&mut metadata, 3);
+        assert!(metadata.is_full());
+        assert!(metadata.advance_write_head().is_err());
+        assert_eq!(read_head(&metadata, DEFAULT_CONSUMER), (0, 3));
+        assert_eq!(metadata.write_head, 0);
+    }
+}
+
+#[test]
+fn overwrite_oldest_moves_the_read_head() {
+    let mut metadata = SensorFileMetadata::from_size(3);
+    metadata.policy = FullPolicy::OverwriteOldest;
+    fill(&mut metadata, 5);
+    assert!(metadata.is_full());
+    assert_eq!(metadata.len(), 3);
+    // samples 0 and 1 were overwritten, the oldest left is sample 2
+    assert_eq!(read_head(&metadata, DEFAULT_CONSUMER), (2, 3));
+    assert_eq!(metadata.write_head, 2);
+    metadata.advance_read_head(DEFAULT_CONSUMER).unwrap();
+    assert_eq!(metadata.len(), 2);
+}
+
+#[test]
+fn policy_roundtrip() {
+    let mut file = tempfile::tempfile().unwrap();
+    assert!(read_metadata(&file).unwrap().is_none());
+    let mut metadata = SensorFileMetadata::from_size(4);
+    metadata.policy = FullPolicy::Block;
+    write_metadata(&file, &mut metadata).unwrap();
+    assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), DATA_OFFSET);
+    assert_eq!(
+        read_metadata(&file).unwrap().unwrap().policy,
+        FullPolicy::Block
+    );
+}
+
+#[test]
+fn consumers_read_independently() {
+    let mut metadata = SensorFileMetadata::from_size(4);
+    fill(&mut metadata, 1);
+    metadata.register("fast").unwrap();
+    // a new consumer does not see what was written before it registered
+    assert_eq!(read_head(&metadata, "fast"), (1, 0));
+    fill(&mut metadata, 2);
+    metadata.advance_read_head("fast").unwrap();
+    metadata.advance_read_head("fast").unwrap();
+    assert!(metadata.advance_read_head("fast").is_err());
+    assert_eq!(read_head(&metadata, "fast"), (3, 0));
+    assert_eq!(read_head(&metadata, DEFAULT_CONSUMER), (0, 3));
+
+    // the slowest consumer blocks the producer
+    fill(&mut metadata, 1);
+    assert!(metadata.is_full());
+    assert!(metadata.advance_write_head().is_err());
+    metadata.unregister(DEFAULT_CONSUMER).unwrap();
+    assert!(!metadata.is_full());
+    assert_eq!(metadata.len(), 1);
+    fill(&mut metadata, 3);
+    assert!(metadata.is_full());
+}
+
+#[test]
+fn overwrite_only_moves_the_consumers_that_are_behind() {
+    let mut metadata = SensorFileMetadata::from_size(2);
+    metadata.policy = FullPolicy::OverwriteOldest;
+    metadata.register("fast").unwrap();
+    fill(&mut metadata, 2);
+    metadata.advance_read_head("fast").unwrap();
+    fill(&mut metadata, 1);
+    assert_eq!(read_head(&metadata, DEFAULT_CONSUMER), (1, 2));
+    assert_eq!(read_head(&metadata, "fast"), (1, 2));
+}
+
+#[test]
+fn registration_errors() {
+    let mut metadata = SensorFileMetadata::from_size(4);
+    assert!(metadata.register(DEFAULT_CONSUMER).is_err());
+    assert!(metadata.register("").is_err());
+    assert!(metadata.register("a name that is too long").is_err());
+    assert!(metadata.unregister("nobody").is_err());
+    assert!(metadata.advance_read_head("nobody").is_err());
+    for i in 1..MAX_CONSUMERS {
+        metadata.register(&format!("consumer{}", i)).unwrap();
+    }
+    assert!(metadata.register("one more").is_err());
+    metadata.unregister("consumer3").unwrap();
+    metadata.register("one more").unwrap();
+    assert_eq!(metadata.cursors().count(), MAX_CONSUMERS);
+    let (decoded, _) = SensorFileMetadata::from_slice(&metadata.to_vec().unwrap()).unwrap();
+    let names: Vec<_> = decoded.cursors().map(|c| c.name().into_owned()).collect();
+    assert!(names.contains(&"one more".to_string()));
+    assert!(!names.contains(&"consumer3".to_string()));
+}
+
+#[test]
+fn producers_in_parallel() {
+    const PRODUCERS: u32 = 4;
+    const SAMPLES: u32 = 25;
+    let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|producer| {
+            let path = path.to_path_buf();
+            std::thread::spawn(move || {
+                // every producer has its own file handle, as separate processes would
+                let file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(path)
+                    .unwrap();
+                for _ in 0..SAMPLES {
+                    sensor_lock_file(&file).unwrap();
+                    let mut metadata = read_metadata(&file)
+                        .unwrap()
+                        .unwrap_or_else(|| SensorFileMetadata::from_size(1000));
+                    assert!(
+                        write_sample(&file, &mut metadata, &simulate_sensor(producer)).unwrap()
+                    );
+                    write_metadata(&file, &mut metadata).unwrap();
+                    sensor_unlock_file(&file).unwrap();
+                }
+            })
+        })
+        .collect();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    let mut metadata = read_metadata(&file).unwrap().unwrap();
+    assert_eq!(metadata.len(), (PRODUCERS * SAMPLES) as u64);
+    let mut per_producer = [0; PRODUCERS as usize];
+    while let Some(sample) = read_sample(&file, &mut metadata, DEFAULT_CONSUMER).unwrap() {
+        per_producer[sample.seq() as usize] += 1;
+    }
+    assert_eq!(per_producer, [SAMPLES; PRODUCERS as usize]);
+}
@@ -0,0 +1,147 @@
+use sensors::{
+    FullPolicy, MemoryRing, Metrics, MetricsServer, SensorData, DEFAULT_CONSUMER,
+    OPENMETRICS_CONTENT_TYPE,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+fn sample(seq: u32, timestamp: u32) -> SensorData {
+    SensorData::new(seq, [0.5; 10], timestamp)
+}
+
+fn samples(text: &str) -> Vec<&str> {
+    text.lines().filter(|l| !l.starts_with('#')).collect()
+}
+
+#[test]
+fn buffer_health_is_exposed() {
+    let ring = MemoryRing::new(3).unwrap();
+    let metrics = Metrics::new();
+    for timestamp in 100..104 {
+        let policy = Some(FullPolicy::DropNewest);
+        metrics
+            .produce(&ring, &sample(timestamp % 2, timestamp), 3, policy)
+            .unwrap();
+    }
+    let (read, _) = metrics.consume(&ring, DEFAULT_CONSUMER, 2).unwrap();
+    assert_eq!(read.len(), 2);
+    let text = metrics.render_at(110);
+    assert!(text.starts_with("# TYPE sensors_buffer_size gauge\n"));
+    assert!(text.ends_with("\n# EOF\n"));
+    let lock_waits = "sensors_lock_wait_seconds_count 5";
+    assert_eq!(
+        samples(&text)
+            .into_iter()
+            // the sum and the rates depend on timing, checked on their own
+            .filter(|l| !l.starts_with("sensors_lock_wait_seconds_sum"))
+            .filter(|l| !l.contains("_per_second"))
+            .collect::<Vec<_>>(),
+        [
+            "sensors_buffer_size 3",
+            "sensors_current_size 1",
+            "sensors_unread_samples{consumer=\"default\"} 1",
+            "sensors_written_samples_total 3",
+            "sensors_dropped_samples_total{policy=\"drop_newest\"} 1",
+            "sensors_blocked_writes_total 0",
+            "sensors_consumed_samples_total{consumer=\"default\"} 2",
+            lock_waits,
+            // the sample of sensor 1 at 103 was dropped
+            "sensors_last_sample_age_seconds{sensor=\"0\"} 8",
+            "sensors_last_sample_age_seconds{sensor=\"1\"} 9",
+        ]
+    );
+}
+
+#[test]
+fn overwritten_samples_count_as_dropped() {
+    let ring = MemoryRing::new(2).unwrap();
+    let metrics = Metrics::new();
+    for timestamp in 0..5 {
+        let policy = Some(FullPolicy::OverwriteOldest);
+        metrics
+            .produce(&ring, &sample(0, timestamp), 2, policy)
+            .unwrap();
+    }
+    metrics
+        .produce(&ring, &sample(0, 5), 2, Some(FullPolicy::Block))
+        .unwrap();
+    let text = metrics.render_at(5);
+    let lines = samples(&text);
+    assert!(lines.contains(&"sensors_written_samples_total 5"));
+    assert!(lines.contains(&"sensors_dropped_samples_total{policy=\"overwrite_oldest\"} 3"));
+    assert!(lines.contains(&"sensors_blocked_writes_total 1"));
+    assert!(lines.contains(&"sensors_last_sample_age_seconds{sensor=\"0\"} 1"));
+}
+
+fn rate(text: &str, name: &str) -> f64 {
+    let line = samples(text)
+        .into_iter()
+        .find(|l| l.starts_with(name))
+        .unwrap();
+    line.rsplit(' ').next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn rates_are_over_the_time_since_the_previous_scrape() {
+    let ring = MemoryRing::new(10).unwrap();
+    let metrics = Metrics::new();
+    let start = std::time::Instant::now();
+    for timestamp in 0..4 {
+        metrics
+            .produce(&ring, &sample(0, timestamp), 10, None)
+            .unwrap();
+    }
+    metrics.consume(&ring, DEFAULT_CONSUMER, 2).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let text = metrics.render_at(4);
+    let elapsed = start.elapsed().as_secs_f64();
+    let written = rate(&text, "sensors_written_samples_per_second ");
+    assert!(written > 0.0 && written <= 4.0 / 0.1, "{}", written);
+    assert!(written >= 4.0 / elapsed, "{}", written);
+    let consumed = rate(
+        &text,
+        "sensors_consumed_samples_per_second{consumer=\"default\"}",
+    );
+    assert!(consumed > 0.0 && consumed <= 2.0 / 0.1, "{}", consumed);
+
+    // one sample written and none read since that scrape
+    metrics.produce(&ring, &sample(0, 4), 10, None).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let text = metrics.render_at(5);
+    let written = rate(&text, "sensors_written_samples_per_second ");
+    assert!(written > 0.0 && written <= 1.0 / 0.1, "{}", written);
+    let consumed = rate(
+        &text,
+        "sensors_consumed_samples_per_second{consumer=\"default\"}",
+    );
+    assert_eq!(consumed, 0.0);
+}
+
+fn get(address: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn metrics_are_served_over_http() {
+    let metrics = Arc::new(Metrics::new());
+    let server = MetricsServer::bind("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+    let address = server.local_addr().unwrap().to_string();
+    server.spawn();
+    metrics.consumed("remote", &[sample(0, 0)], None);
+
+    let response = get(&address, "GET /metrics HTTP/1.1\r\nHost: sensors\r\n\r\n");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains(&format!("Content-Type: {}", OPENMETRICS_CONTENT_TYPE)));
+    assert!(head.contains(&format!("Content-Length: {}", body.len())));
+    assert!(body.contains("sensors_consumed_samples_total{consumer=\"remote\"} 1\n"));
+    assert!(body.ends_with("# EOF\n"));
+
+    assert!(get(&address, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    assert!(get(&address, "POST /metrics HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+}
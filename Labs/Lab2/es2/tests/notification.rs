@@ -0,0 +1,59 @@
+use sensors::FileNotifier;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// the notifier only watches the path, any file will do
+fn setup() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sensor_data.bin");
+    std::fs::write(&path, [0; 16]).unwrap();
+    (dir, path)
+}
+
+// writes through its own file handle, as the other process does
+fn write(path: &Path) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.write_all(&[1; 8]).unwrap();
+}
+
+#[test]
+fn a_write_from_another_thread_wakes_the_waiting_side() {
+    let (_dir, path) = setup();
+    let notifier = FileNotifier::watch(&path).unwrap();
+    notifier.clear();
+    let writer = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            write(&path);
+        })
+    };
+    let start = Instant::now();
+    assert!(notifier.wait(Duration::from_secs(10)));
+    // woken by the write, long before the timeout
+    assert!(start.elapsed() < Duration::from_secs(5));
+    writer.join().unwrap();
+}
+
+#[test]
+fn a_write_between_clear_and_wait_is_not_missed() {
+    let (_dir, path) = setup();
+    let notifier = FileNotifier::watch(&path).unwrap();
+    notifier.clear();
+    write(&path);
+    let start = Instant::now();
+    assert!(notifier.wait(Duration::from_secs(10)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn wait_times_out_without_writes() {
+    let (_dir, path) = setup();
+    let notifier = FileNotifier::watch(&path).unwrap();
+    notifier.clear();
+    let start = Instant::now();
+    assert!(!notifier.wait(Duration::from_millis(200)));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
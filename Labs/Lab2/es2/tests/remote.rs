@@ -0,0 +1,233 @@
+use binary_io::BinPack;
+use sensors::{
+    read_metadata, sensor_lock_file, sensor_unlock_file, write_metadata, write_sample, Client,
+    Endpoint, FullPolicy, Request, SensorData, SensorDataError, SensorFileMetadata, Server,
+    DEFAULT_CONSUMER,
+};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+fn sample(timestamp: u32) -> SensorData {
+    SensorData::new(timestamp % 3, [timestamp as f32; 10], timestamp)
+}
+
+fn open(path: &Path) -> File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .unwrap()
+}
+
+// writes a sample as the producer does, with the file locked
+fn produce(path: &Path, timestamp: u32) {
+    let file = open(path);
+    sensor_lock_file(&file).unwrap();
+    let mut metadata = read_metadata(&file)
+        .unwrap()
+        .unwrap_or_else(|| SensorFileMetadata::from_size(16));
+    assert!(write_sample(&file, &mut metadata, &sample(timestamp)).unwrap());
+    write_metadata(&file, &mut metadata).unwrap();
+    sensor_unlock_file(&file).unwrap();
+}
+
+fn unread(path: &Path) -> u64 {
+    let metadata = read_metadata(&open(path)).unwrap().unwrap();
+    metadata.cursor(DEFAULT_CONSUMER).unwrap().len()
+}
+
+// acks have no answer, the server applies them a little later
+fn wait_for_unread(path: &Path, expected: u64) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while unread(path) != expected {
+        assert!(Instant::now() < deadline, "{} unread", unread(path));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn serve(listen: &Endpoint, path: &Path) -> Endpoint {
+    let server = Server::bind(listen, path).unwrap();
+    let endpoint = server.endpoint().unwrap();
+    std::thread::spawn(move || server.run().unwrap());
+    endpoint
+}
+
+fn setup(timestamps: impl IntoIterator<Item = u32>) -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sensor_data.bin");
+    let file = open(&path);
+    write_metadata(&file, &mut SensorFileMetadata::from_size(16)).unwrap();
+    for timestamp in timestamps {
+        produce(&path, timestamp);
+    }
+    (dir, path)
+}
+
+fn bytes(sample: Option<SensorData>) -> Vec<u8> {
+    sample.unwrap().to_vec().unwrap()
+}
+
+#[test]
+fn endpoints() {
+    #[cfg(unix)]
+    assert_eq!(
+        "unix:/tmp/sensors.sock".parse(),
+        Ok(Endpoint::Unix("/tmp/sensors.sock".into()))
+    );
+    #[cfg(not(unix))]
+    assert!("unix:/tmp/sensors.sock".parse::<Endpoint>().is_err());
+    assert_eq!(
+        "tcp:127.0.0.1:7878".parse(),
+        Ok(Endpoint::Tcp("127.0.0.1:7878".to_string()))
+    );
+    assert_eq!(
+        "localhost:7878".parse(),
+        Ok(Endpoint::Tcp("localhost:7878".to_string()))
+    );
+    assert!("unix:".parse::<Endpoint>().is_err());
+    let request = Request::Subscribe {
+        name: "remote".to_string(),
+    };
+    let encoded = request.to_vec().unwrap();
+    assert_eq!(encoded, b"\x00\x06remote");
+    assert_eq!(Request::from_slice(&encoded).unwrap().0, request);
+}
+
+#[test]
+fn samples_stay_unread_until_acknowledged() {
+    let (_dir, path) = setup(100..103);
+    let endpoint = serve(&"127.0.0.1:0".parse().unwrap(), &path);
+    let mut client = Client::connect(&endpoint).unwrap();
+    assert_eq!(client.subscribe(DEFAULT_CONSUMER).unwrap(), 3);
+    for timestamp in 100..103 {
+        assert_eq!(
+            bytes(client.next_sample().unwrap()),
+            bytes(Some(sample(timestamp)))
+        );
+    }
+    assert_eq!(unread(&path), 3);
+    client.ack(2).unwrap();
+    wait_for_unread(&path, 1);
+
+    // the sample that was not acknowledged is sent again
+    drop(client);
+    let mut client = Client::connect(&endpoint).unwrap();
+    assert_eq!(client.subscribe(DEFAULT_CONSUMER).unwrap(), 1);
+    assert_eq!(
+        bytes(client.next_sample().unwrap()),
+        bytes(Some(sample(102)))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn new_samples_are_streamed_over_a_unix_socket() {
+    let (dir, path) = setup([]);
+    let endpoint = serve(&Endpoint::Unix(dir.path().join("sensors.sock")), &path);
+    let mut client = Client::connect(&endpoint).unwrap();
+    assert_eq!(client.subscribe(DEFAULT_CONSUMER).unwrap(), 0);
+    for timestamp in [200, 201] {
+        produce(&path, timestamp);
+        assert_eq!(
+            bytes(client.next_sample().unwrap()),
+            bytes(Some(sample(timestamp)))
+        );
+    }
+    client.ack(2).unwrap();
+    wait_for_unread(&path, 0);
+}
+
+#[test]
+fn samples_overwritten_in_flight_are_not_acknowledged_twice() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sensor_data.bin");
+    let mut metadata = SensorFileMetadata::from_size(4);
+    metadata.policy = FullPolicy::OverwriteOldest;
+    write_metadata(&open(&path), &mut metadata).unwrap();
+    for timestamp in 100..104 {
+        produce(&path, timestamp);
+    }
+    let endpoint = serve(&"127.0.0.1:0".parse().unwrap(), &path);
+    let mut client = Client::connect(&endpoint).unwrap();
+    assert_eq!(client.subscribe(DEFAULT_CONSUMER).unwrap(), 4);
+    for timestamp in 100..104 {
+        assert_eq!(
+            client.next_sample().unwrap().unwrap().timestamp(),
+            timestamp
+        );
+    }
+    // 100 and 101 are overwritten while in flight
+    produce(&path, 104);
+    produce(&path, 105);
+    // they were gone already, only 102 is taken off the cursor
+    client.ack(3).unwrap();
+    wait_for_unread(&path, 3);
+    // the new samples are still sent
+    for timestamp in [104, 105] {
+        assert_eq!(
+            client.next_sample().unwrap().unwrap().timestamp(),
+            timestamp
+        );
+    }
+    client.ack(3).unwrap();
+    wait_for_unread(&path, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn binding_over_a_regular_file_leaves_it_alone() {
+    let (dir, path) = setup(100..101);
+    let before = std::fs::read(&path).unwrap();
+    match Server::bind(&Endpoint::Unix(path.clone()), dir.path().join("other.bin")) {
+        Err(SensorDataError::IoError(e)) => assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+
+    // a socket left behind is replaced
+    let socket = dir.path().join("sensors.sock");
+    drop(Server::bind(&Endpoint::Unix(socket.clone()), &path).unwrap());
+    assert!(socket.exists());
+    Server::bind(&Endpoint::Unix(socket), &path).unwrap();
+}
+
+#[test]
+fn seek_by_timestamp() {
+    let (_dir, path) = setup(100..106);
+    let endpoint = serve(&"127.0.0.1:0".parse().unwrap(), &path);
+    let mut client = Client::connect(&endpoint).unwrap();
+    client.subscribe(DEFAULT_CONSUMER).unwrap();
+    assert_eq!(client.seek(103).unwrap(), 3);
+    assert_eq!(unread(&path), 3);
+    assert_eq!(client.next_sample().unwrap().unwrap().timestamp(), 103);
+    // nothing is left to skip
+    assert_eq!(client.seek(50).unwrap(), 0);
+    assert_eq!(client.next_sample().unwrap().unwrap().timestamp(), 103);
+    assert_eq!(client.seek(1000).unwrap(), 3);
+    assert_eq!(unread(&path), 0);
+}
+
+#[test]
+fn errors_are_reported_to_the_client() {
+    let (_dir, path) = setup(100..101);
+    let endpoint = serve(&"127.0.0.1:0".parse().unwrap(), &path);
+    let mut client = Client::connect(&endpoint).unwrap();
+    match client.subscribe("nobody") {
+        Err(SensorDataError::RemoteError(message)) => assert!(message.contains("nobody")),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert!(client.seek(0).is_err());
+    client.subscribe(DEFAULT_CONSUMER).unwrap();
+    client.next_sample().unwrap().unwrap();
+    client.ack(2).unwrap();
+    assert!(matches!(
+        client.next_sample(),
+        Err(SensorDataError::RemoteError(_))
+    ));
+    // the connection is still usable
+    client.ack(1).unwrap();
+    wait_for_unread(&path, 0);
+}
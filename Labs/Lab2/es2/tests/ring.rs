@@ -0,0 +1,371 @@
+use sensors::{
+    consume, produce, with_lock, FullPolicy, MappedRing, MemoryRing, Metrics, Produced, SensorData,
+    SensorDataError, SensorFileMetadata, SensorRing, DEFAULT_CONSUMER, SHM_DIR,
+};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+type Ring = Arc<dyn SensorRing + Send + Sync>;
+
+enum Kind {
+    File,
+    Mapped,
+    Shm,
+    Memory(Ring),
+}
+
+// a ring of every kind. file backed rings lock per open file, so every thread opens its own
+struct Backend {
+    kind: Kind,
+    path: PathBuf,
+    size: u64,
+    _dir: tempfile::TempDir,
+}
+
+impl Backend {
+    fn open(&self) -> Ring {
+        match &self.kind {
+            Kind::File => Arc::new(open(&self.path)),
+            Kind::Mapped | Kind::Shm => Arc::new(MappedRing::open(&self.path, self.size).unwrap()),
+            Kind::Memory(ring) => Arc::clone(ring),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self.kind {
+            Kind::File => "file",
+            Kind::Mapped => "mapped",
+            Kind::Shm => "shm",
+            Kind::Memory(_) => "memory",
+        }
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        if matches!(self.kind, Kind::Shm) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn open(path: &Path) -> File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .unwrap()
+}
+
+fn backends(size: u64) -> Vec<Backend> {
+    static SHM_RINGS: AtomicUsize = AtomicUsize::new(0);
+    let shm = format!(
+        "sensors-test-{}-{}",
+        std::process::id(),
+        SHM_RINGS.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut backends = vec![];
+    for kind in [
+        Kind::File,
+        Kind::Mapped,
+        Kind::Shm,
+        Kind::Memory(Arc::new(MemoryRing::new(size).unwrap())),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = match kind {
+            Kind::Shm => Path::new(SHM_DIR).join(&shm),
+            _ => dir.path().join("sensor_data.bin"),
+        };
+        backends.push(Backend {
+            kind,
+            path,
+            size,
+            _dir: dir,
+        });
+    }
+    backends
+}
+
+fn sample(timestamp: u32) -> SensorData {
+    SensorData::new(timestamp % 10, [timestamp as f32; 10], timestamp)
+}
+
+fn timestamps(samples: &[SensorData]) -> Vec<u32> {
+    samples.iter().map(|s| s.timestamp()).collect()
+}
+
+fn register(ring: &dyn SensorRing, name: &str, size: u64) {
+    with_lock(ring, || {
+        let mut metadata = ring
+            .load_metadata()?
+            .unwrap_or_else(|| SensorFileMetadata::from_size(size));
+        metadata.register(name)?;
+        ring.store_metadata(&mut metadata)
+    })
+    .unwrap();
+}
+
+#[test]
+fn samples_are_read_in_order() {
+    for backend in backends(8) {
+        let ring = backend.open();
+        for timestamp in 100..105 {
+            let (produced, _) = produce(&*ring, &sample(timestamp), 8, None).unwrap();
+            assert_eq!(produced, Produced::Written, "{}", backend.name());
+        }
+        let (samples, metadata) = consume(&*ring, DEFAULT_CONSUMER, 3).unwrap();
+        assert_eq!(timestamps(&samples), [100, 101, 102], "{}", backend.name());
+        assert_eq!(metadata.unwrap().len(), 2);
+        let (samples, _) = consume(&*ring, DEFAULT_CONSUMER, 10).unwrap();
+        assert_eq!(timestamps(&samples), [103, 104], "{}", backend.name());
+        assert!(consume(&*ring, DEFAULT_CONSUMER, 10).unwrap().0.is_empty());
+    }
+}
+
+#[test]
+fn full_buffer_policies() {
+    for (policy, last, expected) in [
+        (FullPolicy::DropNewest, Produced::Dropped, [0, 1, 2]),
+        (FullPolicy::OverwriteOldest, Produced::Overwritten, [1, 2, 3]),
+        (FullPolicy::Block, Produced::Blocked, [0, 1, 2]),
+    ] {
+        for backend in backends(3) {
+            let ring = backend.open();
+            for timestamp in 0..3 {
+                produce(&*ring, &sample(timestamp), 3, Some(policy)).unwrap();
+            }
+            let (produced, metadata) = produce(&*ring, &sample(3), 3, Some(policy)).unwrap();
+            assert_eq!(produced, last, "{} {:?}", backend.name(), policy);
+            assert!(metadata.is_full());
+            let (samples, _) = consume(&*ring, DEFAULT_CONSUMER, 10).unwrap();
+            assert_eq!(
+                timestamps(&samples),
+                expected,
+                "{} {:?}",
+                backend.name(),
+                policy
+            );
+        }
+    }
+}
+
+#[test]
+fn consumers_read_independently() {
+    for backend in backends(8) {
+        let ring = backend.open();
+        produce(&*ring, &sample(0), 8, None).unwrap();
+        produce(&*ring, &sample(1), 8, None).unwrap();
+        // starts at the write head
+        register(&*ring, "second", 8);
+        produce(&*ring, &sample(2), 8, None).unwrap();
+        let (samples, _) = consume(&*ring, DEFAULT_CONSUMER, 10).unwrap();
+        assert_eq!(timestamps(&samples), [0, 1, 2], "{}", backend.name());
+        let (samples, _) = consume(&*ring, "second", 10).unwrap();
+        assert_eq!(timestamps(&samples), [2], "{}", backend.name());
+        match consume(&*ring, "nobody", 10) {
+            Err(SensorDataError::IoError(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::NotFound)
+            }
+            other => panic!("{}: unexpected {:?}", backend.name(), other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn producer_and_consumer_threads() {
+    const SAMPLES: u32 = 40;
+    for backend in backends(4) {
+        let producer_ring = backend.open();
+        let producer = std::thread::spawn(move || {
+            for timestamp in 0..SAMPLES {
+                while produce(
+                    &*producer_ring,
+                    &sample(timestamp),
+                    4,
+                    Some(FullPolicy::Block),
+                )
+                .unwrap()
+                .0 == Produced::Blocked
+                {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+        let ring = backend.open();
+        let mut read = vec![];
+        while read.len() < SAMPLES as usize {
+            let (samples, _) = consume(&*ring, DEFAULT_CONSUMER, 3).unwrap();
+            if samples.is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            read.extend(samples);
+        }
+        producer.join().unwrap();
+        assert_eq!(
+            timestamps(&read),
+            (0..SAMPLES).collect::<Vec<_>>(),
+            "{}",
+            backend.name()
+        );
+    }
+}
+
+#[test]
+fn file_and_mapped_rings_share_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sensor_data.bin");
+    let file = open(&path);
+    produce(&file, &sample(0), 4, None).unwrap();
+    // the records the file has not reached yet are filled in
+    let mapped = MappedRing::open(&path, 4).unwrap();
+    assert_eq!(file.metadata().unwrap().len(), sensors::record_offset(4));
+    produce(&mapped, &sample(1), 4, None).unwrap();
+    produce(&file, &sample(2), 4, None).unwrap();
+    let (samples, _) = consume(&mapped, DEFAULT_CONSUMER, 2).unwrap();
+    assert_eq!(timestamps(&samples), [0, 1]);
+    let (samples, _) = consume(&file, DEFAULT_CONSUMER, 2).unwrap();
+    assert_eq!(timestamps(&samples), [2]);
+}
+
+#[test]
+fn memory_ring_timed_push_and_pop() {
+    let ring = MemoryRing::new(2).unwrap();
+    let wait = Duration::from_millis(20);
+    assert_eq!(ring.pop_timeout(DEFAULT_CONSUMER, wait).unwrap(), None);
+    assert!(ring.push_timeout(&sample(0), wait).unwrap());
+    assert!(ring.push_timeout(&sample(1), wait).unwrap());
+    // full, the ring waits for room by default
+    assert!(!ring.push_timeout(&sample(2), wait).unwrap());
+    assert_eq!(
+        ring.pop_timeout(DEFAULT_CONSUMER, wait).unwrap(),
+        Some(sample(0))
+    );
+    assert!(ring.push_timeout(&sample(2), wait).unwrap());
+    assert_eq!(
+        timestamps(&ring.pop_up_to(DEFAULT_CONSUMER, 5).unwrap()),
+        [1, 2]
+    );
+}
+
+#[test]
+fn memory_ring_push_follows_the_policy() {
+    let wait = Duration::from_secs(10);
+    let ring = MemoryRing::new(2)
+        .unwrap()
+        .with_policy(FullPolicy::OverwriteOldest);
+    for timestamp in 0..3 {
+        assert!(ring.push_timeout(&sample(timestamp), wait).unwrap());
+    }
+    assert_eq!(
+        timestamps(&ring.pop_up_to(DEFAULT_CONSUMER, 5).unwrap()),
+        [1, 2]
+    );
+    let ring = MemoryRing::new(2)
+        .unwrap()
+        .with_policy(FullPolicy::DropNewest);
+    for timestamp in 0..2 {
+        ring.push(&sample(timestamp)).unwrap();
+    }
+    // dropped at once instead of waiting for room
+    assert!(!ring.push_timeout(&sample(2), wait).unwrap());
+    assert_eq!(
+        timestamps(&ring.pop_up_to(DEFAULT_CONSUMER, 5).unwrap()),
+        [0, 1]
+    );
+}
+
+#[test]
+fn memory_ring_needs_room_for_a_sample() {
+    assert!(matches!(
+        MemoryRing::new(0),
+        Err(SensorDataError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn memory_ring_blocks_until_the_other_thread_moves() {
+    const SAMPLES: u32 = 40;
+    let ring = Arc::new(MemoryRing::new(4).unwrap());
+    let producer_ring = Arc::clone(&ring);
+    let producer = std::thread::spawn(move || {
+        for timestamp in 0..SAMPLES {
+            producer_ring.push(&sample(timestamp)).unwrap();
+        }
+        producer_ring.shutdown();
+    });
+    let mut read = vec![];
+    loop {
+        let samples = ring.pop_up_to(DEFAULT_CONSUMER, 3).unwrap();
+        if samples.is_empty() {
+            break;
+        }
+        assert!(samples.len() <= 3);
+        read.extend(samples);
+    }
+    producer.join().unwrap();
+    assert_eq!(timestamps(&read), (0..SAMPLES).collect::<Vec<_>>());
+}
+
+#[test]
+fn memory_ring_counts_a_blocked_push_once() {
+    let metrics = Arc::new(Metrics::new());
+    let ring = Arc::new(
+        MemoryRing::new(1)
+            .unwrap()
+            .with_metrics(Arc::clone(&metrics)),
+    );
+    ring.push(&sample(0)).unwrap();
+    let pusher = {
+        let ring = Arc::clone(&ring);
+        std::thread::spawn(move || ring.push(&sample(1)).unwrap())
+    };
+    std::thread::sleep(Duration::from_millis(20));
+    // room for a moment, gone again before the woken pusher gets the lock
+    ring.lock().unwrap();
+    let full = ring.load_metadata().unwrap().unwrap();
+    ring.store_metadata(&mut SensorFileMetadata::from_size(1))
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    ring.store_metadata(&mut full.clone()).unwrap();
+    ring.unlock().unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(ring.pop(DEFAULT_CONSUMER).unwrap(), Some(sample(0)));
+    pusher.join().unwrap();
+    assert_eq!(ring.pop(DEFAULT_CONSUMER).unwrap(), Some(sample(1)));
+    let text = metrics.render_at(1);
+    let lines: Vec<_> = text.lines().collect();
+    assert!(lines.contains(&"sensors_blocked_writes_total 1"));
+    assert!(lines.contains(&"sensors_written_samples_total 2"));
+}
+
+#[test]
+fn memory_ring_shutdown_wakes_waiting_threads() {
+    let ring = Arc::new(MemoryRing::new(1).unwrap());
+    ring.push(&sample(0)).unwrap();
+    let pusher = {
+        let ring = Arc::clone(&ring);
+        std::thread::spawn(move || matches!(ring.push(&sample(1)), Err(SensorDataError::ShutDown)))
+    };
+    let empty = Arc::new(MemoryRing::new(1).unwrap());
+    let popper = {
+        let empty = Arc::clone(&empty);
+        std::thread::spawn(move || empty.pop(DEFAULT_CONSUMER).unwrap())
+    };
+    std::thread::sleep(Duration::from_millis(20));
+    ring.shutdown();
+    empty.shutdown();
+    assert!(pusher.join().unwrap());
+    assert_eq!(popper.join().unwrap(), None);
+    // what was pushed before is still there
+    assert_eq!(ring.pop(DEFAULT_CONSUMER).unwrap(), Some(sample(0)));
+    assert_eq!(ring.pop(DEFAULT_CONSUMER).unwrap(), None);
+    assert!(matches!(
+        ring.push(&sample(2)),
+        Err(SensorDataError::ShutDown)
+    ));
+}
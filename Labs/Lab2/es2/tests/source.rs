@@ -0,0 +1,168 @@
+use binary_io::BinPack;
+use sensors::{
+    read_metadata, read_sample, CsvSource, LineSource, RandomSource, SensorData, SensorSource,
+    Waveform, WaveformSource, DEFAULT_CONSUMER,
+};
+use std::process::Command;
+
+fn take(source: &mut impl SensorSource, count: usize) -> Vec<SensorData> {
+    (0..count)
+        .map(|_| source.next_sample().unwrap().unwrap())
+        .collect()
+}
+
+fn bytes(samples: &[SensorData]) -> Vec<Vec<u8>> {
+    samples.iter().map(|s| s.to_vec().unwrap()).collect()
+}
+
+#[test]
+fn seeded_sources_are_reproducible() {
+    let noisy = |seed| WaveformSource::new(Waveform::Sine, 3, 8, 0.1, Some(seed), Some(1000));
+    let first = take(&mut noisy(7), 30);
+    assert_eq!(bytes(&first), bytes(&take(&mut noisy(7), 30)));
+    assert_ne!(bytes(&first), bytes(&take(&mut noisy(8), 30)));
+
+    let random = || RandomSource::new(3, Some(7), Some(1000));
+    assert_eq!(
+        bytes(&take(&mut random(), 30)),
+        bytes(&take(&mut random(), 30))
+    );
+}
+
+#[test]
+fn waveforms() {
+    let mut sine = WaveformSource::new(Waveform::Sine, 2, 4, 0.0, None, Some(1000));
+    let samples = take(&mut sine, 4);
+    // the sensors take turns, one second apart
+    let seqs: Vec<_> = samples.iter().map(|s| (s.seq(), s.timestamp())).collect();
+    assert_eq!(seqs, [(0, 1000), (1, 1001), (0, 1002), (1, 1003)]);
+    assert_eq!(samples[0].values()[0], 0.5);
+    assert!((samples[2].values()[0] - 1.0).abs() < 1e-6);
+
+    let mut step = WaveformSource::new(Waveform::Step, 1, 4, 0.0, None, None);
+    let first: Vec<_> = take(&mut step, 4).iter().map(|s| s.values()[0]).collect();
+    assert_eq!(first, [0.0, 0.0, 1.0, 1.0]);
+
+    let mut ramp = WaveformSource::new(Waveform::Ramp, 1, 4, 0.0, None, None);
+    let sample = take(&mut ramp, 2)[1];
+    assert_eq!(sample.values()[0], 0.25);
+    // the values of a sample are a tenth of a period apart
+    assert!((sample.values()[1] - 0.35).abs() < 1e-6);
+    assert!((sample.values()[9] - 0.15).abs() < 1e-6);
+
+    let mut noisy = WaveformSource::new(Waveform::Ramp, 1, 4, 0.05, Some(1), None);
+    for sample in take(&mut noisy, 20) {
+        assert!(sample.values().iter().all(|v| (-0.05..=1.05).contains(v)));
+    }
+}
+
+#[test]
+fn csv_replay_keeps_the_timestamps() {
+    let csv = "timestamp,seq,val0,val1,val2,val3,val4,val5,val6,val7,val8,val9\n\
+               100,3,0,1,2,3,4,5,6,7,8,9\n\
+               \n\
+               105, 4, 0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5,0.5\n";
+    let mut source = CsvSource::new(csv.as_bytes());
+    let first = source.next_sample().unwrap().unwrap();
+    assert_eq!((first.seq(), first.timestamp()), (3, 100));
+    assert_eq!(first.values()[9], 9.0);
+    let second = source.next_sample().unwrap().unwrap();
+    assert_eq!((second.seq(), second.timestamp()), (4, 105));
+    assert!(source.next_sample().unwrap().is_none());
+}
+
+#[test]
+fn malformed_lines_are_reported() {
+    let mut source = CsvSource::new("1,0,1,2,3\n".as_bytes());
+    let error = format!("{:?}", source.next_sample().unwrap_err());
+    assert!(error.contains("line 1: expected 10 values, found 3"));
+    let mut source =
+        CsvSource::new("1,0,0,0,0,0,0,0,0,0,0,0\nsoon,0,0,0,0,0,0,0,0,0,0,0\n".as_bytes());
+    source.next_sample().unwrap().unwrap();
+    let error = format!("{:?}", source.next_sample().unwrap_err());
+    assert!(error.contains("line 2: invalid timestamp"));
+    let mut source = LineSource::new("1 2 3 4 5 6 7 8 9 x\n".as_bytes(), 1);
+    assert!(source.next_sample().is_err());
+}
+
+#[test]
+fn line_input() {
+    let input =
+        "1 2 3 4 5 6 7 8 9 10\n\n0.5,0.5,0.5,0.5,0.5, 0.5,0.5,0.5,0.5,0.5\n1 1 1 1 1 1 1 1 1 1\n";
+    let mut source = LineSource::new(input.as_bytes(), 2);
+    let samples = take(&mut source, 3);
+    let seqs: Vec<_> = samples.iter().map(SensorData::seq).collect();
+    assert_eq!(seqs, [0, 1, 0]);
+    assert_eq!(samples[0].values()[9], 10.0);
+    assert_eq!(samples[1].values()[5], 0.5);
+    assert!(source.next_sample().unwrap().is_none());
+}
+
+fn produce(path: &std::path::Path, extra: &[&str]) -> Vec<u8> {
+    let status = Command::new(env!("CARGO_BIN_EXE_producer"))
+        .args([
+            "--file",
+            path.to_str().unwrap(),
+            "--sensors",
+            "3",
+            "--samples",
+            "8",
+        ])
+        .args(["--interval-ms", "0", "--count", "10"])
+        .args(extra)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn seeded_producer_runs_write_the_same_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let seeded = [
+        "--source", "step", "--seed", "42", "--noise", "0.2", "--start", "5000",
+    ];
+    let first = produce(&dir.path().join("first.bin"), &seeded);
+    let second = produce(&dir.path().join("second.bin"), &seeded);
+    assert_eq!(first, second);
+
+    // the buffer of 8 dropped the last two samples
+    let file = std::fs::File::open(dir.path().join("first.bin")).unwrap();
+    let mut metadata = read_metadata(&file).unwrap().unwrap();
+    let mut timestamps = vec![];
+    while let Some(sample) = read_sample(&file, &mut metadata, DEFAULT_CONSUMER).unwrap() {
+        timestamps.push(sample.timestamp());
+    }
+    assert_eq!(timestamps, (5000..5008).collect::<Vec<_>>());
+}
+
+#[test]
+fn stdin_lines_are_written_without_a_pause() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stdin.bin");
+    let mut producer = Command::new(env!("CARGO_BIN_EXE_producer"))
+        .args(["--file", path.to_str().unwrap(), "--sensors", "3"])
+        .args(["--source", "stdin"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let start = std::time::Instant::now();
+    let lines = "1 1 1 1 1 1 1 1 1 1\n".repeat(5);
+    std::io::Write::write_all(&mut producer.stdin.take().unwrap(), lines.as_bytes()).unwrap();
+    assert!(producer.wait().unwrap().success());
+    // four pauses of the default interval would take four seconds
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+
+    let file = std::fs::File::open(&path).unwrap();
+    let mut metadata = read_metadata(&file).unwrap().unwrap();
+    let mut read = 0;
+    while read_sample(&file, &mut metadata, DEFAULT_CONSUMER)
+        .unwrap()
+        .is_some()
+    {
+        read += 1;
+    }
+    assert_eq!(read, 5);
+}
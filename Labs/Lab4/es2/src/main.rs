@@ -1,78 +1,72 @@
 use clap::Parser;
-use es2::{simulate_sensor, Args, SensorData, SensorFileMetadata, SensorsBuffer};
-use std::sync::{Arc, Mutex, MutexGuard};
+use es2::{
+    simulate_sensor, Args, Metrics, MetricsServer, SensorRing, SensorsBuffer, DEFAULT_CONSUMER,
+};
+use std::sync::Arc;
 
-type SyncSensorBuffer = Arc<Mutex<SensorsBuffer>>;
-
-fn wait_unlock_resource<T>(resource: &'_ Arc<Mutex<T>>) -> MutexGuard<'_, T> {
-    loop {
-        if let Ok(guard) = resource.try_lock() {
-            return guard;
-        }
-    }
-}
+type SyncSensorBuffer = Arc<SensorsBuffer>;
 
 fn producer(buffer: SyncSensorBuffer, args: Args) {
     let mut sensor_num = 0u32;
-    loop {
-        {
-            // scope to make sure the mutex is dropped and unlocked before sleeping
-            let mut locked_buffer = wait_unlock_resource(&buffer);
-            println!("Read metadata {:?}", locked_buffer.metadata);
-            let d = simulate_sensor(sensor_num);
-            if args.verbose {
-                println!("Writing data {:?}", d);
-            }
-            let index = locked_buffer.metadata.write_head as usize;
-            locked_buffer.buffer[index] = d;
-            match locked_buffer.metadata.advance_write_head() {
-                Ok(_) => (),
-                Err(e) => {
-                    println!("Error: {}", e);
+    let mut written = 0u64;
+    while args.count.is_none_or(|count| written < count) {
+        let d = simulate_sensor(sensor_num);
+        if args.verbose {
+            println!("Writing data {:?}", d);
+        }
+        // waits for the consumer while the buffer is full
+        match buffer.push(&d) {
+            Ok(()) => {
+                if let Ok(Some(metadata)) = buffer.load_metadata() {
+                    println!("After writing: {:?}", metadata)
                 }
             }
-            sensor_num = (sensor_num + 1) % args.sensors;
+            Err(e) => {
+                println!("Error: {:?}", e);
+                // the consumer must not wait for samples that will never come
+                buffer.shutdown();
+                return;
+            }
         }
+        written += 1;
+        sensor_num = (sensor_num + 1) % args.sensors;
         if !args.nowait {
             std::thread::sleep(std::time::Duration::from_millis(1000));
         }
     }
+    // the consumer reads what is left and stops
+    buffer.shutdown();
 }
 
 fn consumer(buffer: SyncSensorBuffer, args: Args) {
     loop {
-        {
-            // scope to make sure the mutex is dropped and unlocked before sleeping
-            let mut locked_buffer = wait_unlock_resource(&buffer);
-            println!("Read metadata {:?}", locked_buffer.metadata);
-            let mut data = vec![];
-            for _ in 0..args.sensors {
-                let d = locked_buffer.buffer[locked_buffer.metadata.read_head as usize];
-                if args.verbose {
-                    println!("Read data {:?}", d);
-                }
-                data.push(d);
-                match locked_buffer.metadata.advance_read_head() {
-                    Ok(_) => (),
-                    Err(e) => {
-                        println!("Error: {}", e);
-                        break;
-                    }
-                }
-                if locked_buffer.metadata.is_empty() {
-                    break;
-                }
+        // waits for the producer while the buffer is empty
+        let data = match buffer.pop_up_to(DEFAULT_CONSUMER, args.sensors as usize) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                // wakes the producer up if it waits for room, its next push fails
+                buffer.shutdown();
+                break;
             }
-            for (i, sensor) in data.iter().enumerate() {
-                println!(
-                    "Sensor {}: min => {:.06}, max => {:.06}, avg => {:.06}",
-                    i,
-                    sensor.min(),
-                    sensor.max(),
-                    sensor.avg()
-                );
+        };
+        if data.is_empty() {
+            break;
+        }
+        for (i, sensor) in data.iter().enumerate() {
+            if args.verbose {
+                println!("Read data {:?}", sensor);
             }
-            println!("After reading: {:?}", locked_buffer.metadata);
+            println!(
+                "Sensor {}: min => {:.06}, max => {:.06}, avg => {:.06}",
+                i,
+                sensor.min(),
+                sensor.max(),
+                sensor.avg()
+            );
+        }
+        if let Ok(Some(metadata)) = buffer.load_metadata() {
+            println!("After reading: {:?}", metadata);
         }
         if !args.nowait {
             std::thread::sleep(std::time::Duration::from_millis(10_000));
@@ -82,10 +76,29 @@ fn consumer(buffer: SyncSensorBuffer, args: Args) {
 
 fn main() {
     let args = Args::parse();
-    let data = Arc::new(Mutex::new(SensorsBuffer {
-        buffer: vec![SensorData::default(); args.samples as usize],
-        metadata: SensorFileMetadata::from_size(args.samples),
-    }));
+    let metrics = Arc::new(Metrics::new());
+    let buffer = match SensorsBuffer::new(args.samples) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            println!("Error: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let data = Arc::new(buffer.with_metrics(Arc::clone(&metrics)));
+    if let Some(address) = &args.metrics {
+        let server = MetricsServer::bind(address, metrics)
+            .and_then(|server| Ok((server.local_addr()?, server)));
+        match server {
+            Ok((local, server)) => {
+                println!("Serving metrics on http://{}/metrics", local);
+                server.spawn();
+            }
+            Err(e) => {
+                println!("Error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     let data_prod = Arc::clone(&data);
     let args_prod = args.clone();
     let consumer_thread = std::thread::spawn(move || {